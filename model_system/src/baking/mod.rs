@@ -0,0 +1,571 @@
+use std::{collections::{BTreeMap, HashMap}, path::{Path, PathBuf}};
+
+use glam::{Vec2, Vec3};
+
+use crate::{
+    format::{Shape, ShapeKinds, ShapeEntry, ModelFile, ModelEntry, ModelDefinition, Coloring, Cull, Side, Bound, VisibilityDefinition},
+    loading::{LoadedModels, LoadedFile, AssetTree, error::{ModelError, ErrorCause, VariableError, span_of}, mesh_files::{load_mesh_file, MeshGroup}},
+    naming::MaterialIdentifier,
+    paths::{resolve, to_asset_path},
+    sdf::{self, surface_nets},
+};
+
+use self::{quad::bound_face, variables::{variable_index, resolve_variable}};
+
+pub mod quad;
+pub mod variables;
+
+///
+/// Models of every tile shape and the shape-less models, by identifier in the order of their files.
+#[derive(Debug, Default)]
+pub struct BakedModels{
+    pub shapes: ShapeKinds<Vec<(MaterialIdentifier, BakedModel)>>,
+    pub models: Vec<(MaterialIdentifier, BakedModel)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BakedModel{
+    pub parts: Vec<BakedPart>,
+    pub tint: bool,
+    pub transparent: bool,
+    pub visibility: VisibilityDefinition,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BakedPart{
+    /// Asset path of the texture, None when it couldn't be resolved
+    pub texture: Option<String>,
+    /// (x, y, width, height) in pixels of the texture
+    pub clip: Option<[u32;4]>,
+    pub cull: Cull,
+    pub geometry: Geometry,
+}
+
+///
+/// In tile coordinates, uvs span the whole texture or clip rect from 0 to 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry{
+    Quad{
+        verts: [Vec3;4],
+        uvs: [Vec2;4],
+        normal: Vec3,
+    },
+    Triangles{
+        verts: Vec<Vec3>,
+        uvs: Vec<Vec2>,
+        normals: Vec<Vec3>,
+        /// Linear rgba per vertex
+        colors: Option<Vec<[f32; 4]>>,
+        indices: Vec<u16>,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Slot{
+    Shape(Shape),
+    Model,
+}
+
+impl Slot{
+    fn entry<'b, 'a>(self, file: &'b ModelFile<'a>) -> Option<&'b ShapeEntry<'a>>{
+        match self{
+            Slot::Shape(shape) => file.shape_kinds.get(shape).as_ref(),
+            Slot::Model => file.model.as_ref(),
+        }
+    }
+
+    fn name(self) -> &'static str{
+        match self{
+            Slot::Shape(shape) => shape.name(),
+            Slot::Model => "model",
+        }
+    }
+}
+
+///
+/// An entry with everything it inherits filled in.
+struct Resolved<'b, 'a>{
+    models: &'b [ModelEntry<'a>],
+    /// Path inside the asset tree of the file declaring `models`
+    models_path: &'a Path,
+    /// The first variable of a name wins, an empty value stands for a texture that couldn't be resolved
+    vars: Vec<(String, String)>,
+    tint: Option<bool>,
+    transparent: Option<bool>,
+    visibility: VisibilityDefinition,
+}
+
+struct Baker<'b, 'a, T>{
+    models: &'b LoadedModels<'a>,
+    tree: &'b T,
+    /// Imported meshes by path inside the asset tree, many materials use the same one
+    meshes: HashMap<PathBuf, Result<Vec<MeshGroup>, String>>,
+    problems: Vec<ModelError>,
+}
+
+impl<'a> LoadedModels<'a>{
+    ///
+    /// Resolves the inherits of every scanned file and turns their entries into geometry, along with every problem found.
+    pub fn bake(&self, tree: &impl AssetTree) -> (BakedModels, Vec<ModelError>){
+        let mut baker = Baker{
+            models: self,
+            tree,
+            meshes: HashMap::new(),
+            problems: self.problems.clone(),
+        };
+        let mut out = BakedModels::default();
+
+        for (identifier, path) in self.entries{
+            let Some((path, _)) = self.files.get_key_value(path.as_path()) else{
+                continue;
+            };
+
+            for shape in Shape::ALL{
+                if let Some(model) = baker.bake(path, Slot::Shape(shape)){
+                    out.shapes.get_mut(shape).push((identifier.clone(), model));
+                }
+            }
+            if let Some(model) = baker.bake(path, Slot::Model){
+                out.models.push((identifier.clone(), model));
+            }
+        }
+
+        //Files inherited by many entries report the same problem every time
+        let mut problems = baker.problems;
+        problems.sort();
+        problems.dedup();
+        (out, problems)
+    }
+}
+
+impl<'b, 'a: 'b, T: AssetTree> Baker<'b, 'a, T>{
+    fn report(&mut self, file: &Path, source: &str, part: Option<&str>, cause: ErrorCause){
+        let span = part.map(|x| span_of(source, x));
+        self.problems.push(ModelError::new(file, span, cause));
+    }
+
+    fn bake(&mut self, path: &'a Path, slot: Slot) -> Option<BakedModel>{
+        let resolved = self.resolve_slot(path, slot, &mut Vec::new()).ok()??;
+        let loaded = &self.models.files[resolved.models_path];
+
+        let mut parts = Vec::new();
+        for entry in resolved.models{
+            self.bake_entry(entry, &resolved, loaded, &mut parts);
+        }
+
+        Some(BakedModel{
+            parts,
+            tint: resolved.tint.unwrap_or(false),
+            transparent: resolved.transparent.unwrap_or(false),
+            visibility: resolved.visibility,
+        })
+    }
+
+    fn loaded(&mut self, path: &Path) -> Result<(&'b LoadedFile<'a>, &'b ModelFile<'a>), ()>{
+        let loaded = &self.models.files[path];
+        match &loaded.model{
+            Ok(model) => Ok((loaded, model)),
+            Err(err) => {
+                self.problems.push(err.clone());
+                Err(())
+            },
+        }
+    }
+
+    ///
+    /// The file `path` inherits from, `stack` holds the files inheriting from `path`.
+    fn parent(&mut self, path: &Path, stack: &[&'a Path]) -> Result<Option<&'a Path>, ()>{
+        let (loaded, model) = self.loaded(path)?;
+        let Some(src) = model.inherits else{
+            return Ok(None);
+        };
+
+        let target = resolve(path, src);
+        let cause = if to_asset_path(&target).is_none(){
+            ErrorCause::OutsideAssetRoot(src.to_owned())
+        }
+        else if target == path || stack.contains(&target.as_path()){
+            ErrorCause::CyclicInherit(target)
+        }
+        else{
+            match self.models.files.get_key_value(target.as_path()){
+                None => ErrorCause::MissingInherit(target),
+                Some((_, LoadedFile{ model: Err(err), .. })) => {
+                    self.problems.push(err.clone());
+                    ErrorCause::BrokenInherit(target)
+                },
+                Some((target, _)) => return Ok(Some(target)),
+            }
+        };
+
+        self.report(loaded.file, loaded.source, Some(src), cause);
+        Err(())
+    }
+
+    ///
+    /// Texture variables of a file and the files it inherits from, for entries whose parent has nothing for their shape.
+    fn file_vars(&mut self, path: &'a Path, stack: &mut Vec<&'a Path>) -> Result<Vec<(String, String)>, ()>{
+        let (loaded, model) = self.loaded(path)?;
+        let mut vars = Vec::new();
+        self.declare(&mut vars, path, loaded, &model.textures);
+
+        if let Some(parent) = self.parent(path, stack)?{
+            stack.push(path);
+            let inherited = self.file_vars(parent, stack);
+            stack.pop();
+            merge(&mut vars, inherited?);
+        }
+        Ok(vars)
+    }
+
+    fn resolve_slot(&mut self, path: &'a Path, slot: Slot, stack: &mut Vec<&'a Path>) -> Result<Option<Resolved<'b, 'a>>, ()>{
+        let (loaded, model) = self.loaded(path)?;
+        let Some(entry) = slot.entry(model) else{
+            return Ok(None);
+        };
+
+        let mut vars = Vec::new();
+        self.declare(&mut vars, path, loaded, &entry.textures);
+        self.declare(&mut vars, path, loaded, &model.textures);
+
+        let inherited = match self.parent(path, stack)?{
+            Some(parent) => {
+                stack.push(path);
+                let inherited = match self.resolve_slot(parent, slot, stack){
+                    Ok(Some(resolved)) => Ok(Some(resolved)),
+                    Ok(None) => self.file_vars(parent, stack).map(|x|{
+                        merge(&mut vars, x);
+                        None
+                    }),
+                    Err(()) => Err(()),
+                };
+                stack.pop();
+                inherited?
+            },
+            None => None,
+        };
+
+        let (models, models_path) = match (&entry.model, &inherited){
+            (Some(models), _) => (models.as_slice(), path),
+            (None, Some(inherited)) => (inherited.models, inherited.models_path),
+            (None, None) => {
+                self.report(loaded.file, loaded.source, None, ErrorCause::MissingModel(slot.name()));
+                return Err(());
+            },
+        };
+
+        let tint = entry.tint.or(model.tint);
+        let transparent = entry.transparent.or(model.transparent);
+        Ok(Some(match inherited{
+            Some(inherited) => {
+                merge(&mut vars, inherited.vars);
+                Resolved{
+                    models,
+                    models_path,
+                    vars,
+                    tint: tint.or(inherited.tint),
+                    transparent: transparent.or(inherited.transparent),
+                    visibility: entry.visibility.or(inherited.visibility),
+                }
+            },
+            None => Resolved{
+                models,
+                models_path,
+                vars,
+                tint,
+                transparent,
+                visibility: entry.visibility,
+            },
+        }))
+    }
+
+    ///
+    /// Adds the variables of `textures` not declared yet, with paths resolved against the file declaring them.
+    fn declare(&mut self, vars: &mut Vec<(String, String)>, path: &Path, loaded: &LoadedFile, textures: &BTreeMap<&str, &str>){
+        for (key, value) in textures{
+            if variable_index(vars, key).is_some(){
+                continue;
+            }
+
+            let value = match texture_value(path, value){
+                Ok(texture) => {
+                    //Labelled textures live inside the file before the '#'
+                    let file = texture.split('#').next().unwrap_or(&texture);
+                    if !texture.starts_with('#') && self.tree.find(Path::new(file)).is_none(){
+                        self.report(loaded.file, loaded.source, Some(value), ErrorCause::MissingTextureFile(file.to_owned()));
+                    }
+                    texture
+                },
+                Err(cause) => {
+                    self.report(loaded.file, loaded.source, Some(value), cause);
+                    String::new()
+                },
+            };
+            vars.push((key.to_string(), value));
+        }
+    }
+
+    fn texture(&mut self, coloring: &Coloring, vars: &[(String, String)], loaded: &LoadedFile) -> (Option<String>, Option<[u32;4]>){
+        let Coloring::UvMapped{ texture, clip } = coloring;
+
+        let resolved = match variable_index(vars, texture){
+            Some(index) => resolve_variable(vars, index),
+            None => Err(VariableError::Unknown(texture.to_string())),
+        };
+
+        match resolved{
+            Ok(x) if !x.is_empty() => (Some(x.to_owned()), *clip),
+            Ok(_) => (None, *clip),
+            Err(err) => {
+                self.report(loaded.file, loaded.source, Some(texture), ErrorCause::Variable(err));
+                (None, *clip)
+            },
+        }
+    }
+
+    fn bake_entry(&mut self, entry: &ModelEntry, resolved: &Resolved, loaded: &LoadedFile, parts: &mut Vec<BakedPart>){
+        let bound = &entry.bound;
+        if bound.size().cmplt(Vec3::ZERO).any(){
+            self.report(loaded.file, loaded.source, None, ErrorCause::InvalidBound);
+            return;
+        }
+
+        match &entry.definition{
+            ModelDefinition::Face{ side, rotation } => {
+                let (texture, clip) = self.texture(&entry.coloring, &resolved.vars, loaded);
+                let (verts, uvs, normal) = bound_face(bound, *side, *rotation);
+                parts.push(BakedPart{
+                    texture,
+                    clip,
+                    cull: entry.cull.unwrap_or(Cull::Never),
+                    geometry: Geometry::Quad{ verts, uvs, normal },
+                });
+            },
+            ModelDefinition::Solid => {
+                let (texture, clip) = self.texture(&entry.coloring, &resolved.vars, loaded);
+                for side in Side::ALL{
+                    let (verts, uvs, normal) = bound_face(bound, side, 0.0);
+                    //Faces on the tile border are covered by a solid neighbour
+                    let on_border = (verts[0].dot(normal) - 0.5).abs() < 0.0001;
+                    let cull = entry.cull.unwrap_or(if on_border { Cull::WhenVisible(side) } else { Cull::Never });
+                    parts.push(BakedPart{
+                        texture: texture.clone(),
+                        clip,
+                        cull,
+                        geometry: Geometry::Quad{ verts, uvs, normal },
+                    });
+                }
+            },
+            ModelDefinition::Mesh{ src, materials } => {
+                self.bake_mesh(src, materials, entry, resolved, loaded, parts);
+            },
+            ModelDefinition::Triangles{ verts, uvs, normals, indices } => {
+                let Coloring::UvMapped{ texture: name, .. } = &entry.coloring;
+                let Some(normals) = normals else{
+                    self.report(loaded.file, loaded.source, Some(name), ErrorCause::MissingNormals);
+                    return;
+                };
+                let (texture, clip) = self.texture(&entry.coloring, &resolved.vars, loaded);
+                let (verts, normals) = into_bound(bound,
+                    verts.iter().map(|x| Vec3::from(*x)).collect(),
+                    normals.iter().map(|x| Vec3::from(*x)).collect(),
+                );
+
+                parts.push(BakedPart{
+                    texture,
+                    clip,
+                    cull: entry.cull.unwrap_or(Cull::Never),
+                    geometry: Geometry::Triangles{
+                        verts,
+                        uvs: uvs.iter().map(|x| Vec2::from(*x)).collect(),
+                        normals,
+                        colors: None,
+                        indices: indices.iter().flatten().copied().collect(),
+                    },
+                });
+            },
+            ModelDefinition::SDF(expression) => {
+                let mesh = match sdf::parse(expression){
+                    Ok(sdf) => surface_nets(&sdf, bound),
+                    Err(err) => {
+                        self.report(loaded.file, loaded.source, Some(&expression[err.offset..]), ErrorCause::Sdf(err.message));
+                        return;
+                    },
+                };
+                if mesh.indices.is_empty(){
+                    self.report(loaded.file, loaded.source, Some(expression), ErrorCause::Sdf("the surface doesn't pass through the bound".to_owned()));
+                    return;
+                }
+                let (texture, clip) = self.texture(&entry.coloring, &resolved.vars, loaded);
+
+                parts.push(BakedPart{
+                    texture,
+                    clip,
+                    cull: entry.cull.unwrap_or(Cull::Never),
+                    geometry: Geometry::Triangles{
+                        verts: mesh.verts,
+                        uvs: mesh.uvs,
+                        normals: mesh.normals,
+                        colors: None,
+                        indices: mesh.indices,
+                    },
+                });
+            },
+        }
+    }
+
+    fn bake_mesh(&mut self, src: &str, materials: &BTreeMap<&str, Coloring>, entry: &ModelEntry, resolved: &Resolved, loaded: &LoadedFile, parts: &mut Vec<BakedPart>){
+        let mesh_path = resolve(resolved.models_path, src);
+        let Some(mesh) = to_asset_path(&mesh_path) else{
+            self.report(loaded.file, loaded.source, Some(src), ErrorCause::OutsideAssetRoot(src.to_owned()));
+            return;
+        };
+
+        let tree = self.tree;
+        let groups = match self.meshes.entry(mesh_path.clone()).or_insert_with(|| match tree.find(&mesh_path){
+            Some(file) => load_mesh_file(&file.to_string_lossy()).map_err(|err| err.to_string()),
+            None => Err(format!("{} not found", mesh)),
+        }){
+            Ok(x) => x.clone(),
+            Err(err) => {
+                let cause = ErrorCause::MeshImport(err.clone());
+                self.report(loaded.file, loaded.source, Some(src), cause);
+                return;
+            },
+        };
+
+        for material in materials.keys(){
+            if !groups.iter().any(|x| x.material.as_deref() == Some(*material)){
+                let cause = ErrorCause::UnusedMaterial{ src: src.to_owned(), material: material.to_string() };
+                self.report(loaded.file, loaded.source, Some(material), cause);
+            }
+        }
+
+        //A bound material wins over the texture embedded in the mesh file, which wins over the entry colouring
+        for group in groups{
+            let (texture, clip) = match (group.material.as_deref().and_then(|x| materials.get(x)), &group.texture){
+                (Some(coloring), _) => self.texture(coloring, &resolved.vars, loaded),
+                (None, Some(label)) => (Some(format!("{}#{}", mesh, label)), None),
+                (None, None) => self.texture(&entry.coloring, &resolved.vars, loaded),
+            };
+            let (verts, normals) = into_bound(&entry.bound, group.verts, group.normals);
+
+            parts.push(BakedPart{
+                texture,
+                clip,
+                cull: entry.cull.unwrap_or(Cull::Never),
+                geometry: Geometry::Triangles{
+                    verts,
+                    uvs: group.uvs,
+                    normals,
+                    colors: group.colors,
+                    indices: group.indices,
+                },
+            });
+        }
+    }
+}
+
+fn merge(vars: &mut Vec<(String, String)>, inherited: Vec<(String, String)>){
+    for (key, value) in inherited{
+        if variable_index(vars, &key).is_none(){
+            vars.push((key, value));
+        }
+    }
+}
+
+///
+/// Turns a texture variable into the path the asset server loads, `#name` references are kept as they are.
+/// Paths are resolved here so inherited variables stay relative to the file declaring them.
+pub fn texture_value(including: &Path, value: &str) -> Result<String, ErrorCause>{
+    if value.starts_with('#'){
+        return Ok(value.to_owned());
+    }
+
+    to_asset_path(&resolve(including, value)).ok_or_else(|| ErrorCause::OutsideAssetRoot(value.to_owned()))
+}
+
+///
+/// Scales geometry made for the whole tile into `bound`.
+fn into_bound(bound: &Bound, verts: Vec<Vec3>, normals: Vec<Vec3>) -> (Vec<Vec3>, Vec<Vec3>){
+    if *bound == Bound::default(){
+        return (verts, normals);
+    }
+
+    let size = bound.size();
+    let center = bound.center();
+    //Normals are scaled the other way, by the cofactors so flat bounds don't divide by zero
+    let normal_scale = Vec3::new(size.y * size.z, size.x * size.z, size.x * size.y);
+
+    (
+        verts.into_iter().map(|x| x * size + center).collect(),
+        normals.into_iter().map(|x| (x * normal_scale).normalize_or_zero()).collect(),
+    )
+}
+
+#[cfg(test)]
+mod test{
+    use std::path::PathBuf;
+
+    use crate::{format::{Cull, Side, Visibility}, loading::{load_models, Folder, error::ErrorCause}};
+
+    use super::Geometry;
+
+    #[test]
+    fn bake_inherited(){
+        //ARRANGE
+        let tree = Folder(PathBuf::from("./test_files/baking"));
+
+        //ACT
+        let (models, problems) = load_models(&tree, "materials");
+
+        //ASSERT
+        let floors = &models.shapes.floor;
+        let walls = &models.shapes.wall;
+        assert!(floors.len() == 2);
+        assert!(walls.len() == 2);
+
+        let (identifier, floor) = &floors[0];
+        assert!(identifier.to_string() == "granite");
+        assert!(floor.tint);
+        assert!(floor.parts.len() == 1);
+        assert!(floor.parts[0].texture.as_deref() == Some("textures/granite.png"));
+
+        let (identifier, wall) = &walls[0];
+        assert!(identifier.to_string() == "granite");
+        assert!(wall.transparent);
+        assert!(wall.visibility.get(Side::Up) == Visibility::Solid);
+        assert!(wall.parts.len() == 6);
+        for (part, side) in wall.parts.iter().zip(Side::ALL){
+            assert!(part.texture.as_deref() == Some("textures/granite.png"));
+            assert!(part.clip == Some([0, 0, 16, 16]));
+            assert!(part.cull == Cull::WhenVisible(side));
+            assert!(matches!(part.geometry, Geometry::Quad{ .. }));
+        }
+
+        let (identifier, wall) = &walls[1];
+        assert!(identifier.to_string() == "");
+        assert!(!wall.transparent);
+        assert!(wall.parts[0].texture.as_deref() == Some("textures/stone.png"));
+
+        assert!(problems.len() == 1);
+        assert!(matches!(&problems[0].cause, ErrorCause::MissingInherit(x) if x.ends_with("missing.json")));
+    }
+
+    #[test]
+    fn meshes_and_inherits_stay_in_the_tree(){
+        //ARRANGE
+        let tree = Folder(PathBuf::from("./test_files/baking"));
+
+        //ACT
+        let (_, mut problems) = load_models(&tree, "escaping");
+
+        //ASSERT
+        problems.sort_by_key(|x| x.path.clone());
+        let causes = problems.iter().map(|x| &x.cause).collect::<Vec<_>>();
+        assert!(causes.len() == 3);
+        assert!(matches!(causes[0], ErrorCause::OutsideAssetRoot(x) if x == "../../../mod.json"));
+        assert!(matches!(causes[1], ErrorCause::MeshImport(x) if x == "models/missing.obj not found"));
+        assert!(matches!(causes[2], ErrorCause::OutsideAssetRoot(x) if x == "../../box.obj"));
+    }
+}
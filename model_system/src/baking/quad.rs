@@ -0,0 +1,153 @@
+use glam::{Vec2, Vec3, Quat};
+
+use crate::format::{Bound, Side};
+
+///
+/// `rotation` is in degrees around the face normal, counter-clockwise when looking at the face.
+/// The uvs stay on their corners, so the texture turns with the quad.
+pub fn create_quad(normal: Side, size: Vec2, position: Vec3, rotation: f32) -> ([Vec3;4], [Vec2;4], Vec3){
+    let [x, y, z] = normal.axes();
+
+    let rotation = Quat::from_axis_angle(y, rotation.to_radians());
+    let x = rotation * x / 2.0;
+    let z = rotation * z / 2.0;
+    let verts = [
+        -x*size.x + -z*size.y + position,
+         x*size.x + -z*size.y + position,
+        -x*size.x +  z*size.y + position,
+         x*size.x +  z*size.y + position,
+    ];
+
+    let uvs = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(0.0, 1.0),
+        Vec2::new(1.0, 1.0),
+    ];
+
+    (verts,uvs,y)
+}
+
+///
+/// Quad covering the `side` face of `bound`.
+pub fn bound_face(bound: &Bound, side: Side, rotation: f32) -> ([Vec3;4], [Vec2;4], Vec3){
+    let [x, y, z] = side.axes();
+    let size = bound.size();
+    let position = bound.center() + y * size.dot(y).abs() / 2.0;
+
+    create_quad(side, Vec2::new(size.dot(x).abs(), size.dot(z).abs()), position, rotation)
+}
+
+#[cfg(test)]
+mod tests{
+    use glam::{Vec2, Vec3};
+
+    use crate::format::{Bound, Side};
+
+    use super::{create_quad, bound_face};
+
+    fn assert_corners(actual: [Vec3;4], expected: [Vec3;4]){
+        for (a, e) in actual.iter().zip(expected.iter()){
+            assert!(a.abs_diff_eq(*e, 0.0001), "expected {:?}, got {:?}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn no_rotation_keeps_corners(){
+        //ARRANGE
+        let size = Vec2::ONE;
+
+        //ACT
+        let (verts, uvs, normal) = create_quad(Side::Up, size, Vec3::ZERO, 0.0);
+
+        //ASSERT
+        assert_corners(verts, [
+            Vec3::new(-0.5, 0.0, -0.5),
+            Vec3::new( 0.5, 0.0, -0.5),
+            Vec3::new(-0.5, 0.0,  0.5),
+            Vec3::new( 0.5, 0.0,  0.5),
+        ]);
+        assert_eq!(uvs, [Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0), Vec2::new(1.0, 1.0)]);
+        assert_eq!(normal, Vec3::Y);
+    }
+
+    #[test]
+    fn quarter_turn_rotates_positions_keeping_corner_uvs(){
+        //ARRANGE
+        let position = Vec3::new(0.0, -0.5, 0.0);
+
+        //ACT
+        let (verts, uvs, normal) = create_quad(Side::Up, Vec2::ONE, position, 90.0);
+
+        //ASSERT
+        assert_corners(verts, [
+            Vec3::new(-0.5, -0.5,  0.5),
+            Vec3::new(-0.5, -0.5, -0.5),
+            Vec3::new( 0.5, -0.5,  0.5),
+            Vec3::new( 0.5, -0.5, -0.5),
+        ]);
+        assert_eq!(uvs[0], Vec2::new(0.0, 0.0));
+        assert_eq!(normal, Vec3::Y);
+    }
+
+    #[test]
+    fn diagonal_rotation_stays_on_face_plane(){
+        //ARRANGE
+        let position = Vec3::new(0.4, 0.0, 0.0);
+
+        //ACT
+        let (verts, _, normal) = create_quad(Side::Left, Vec2::ONE, position, 45.0);
+
+        //ASSERT
+        let half_diagonal = 0.5_f32.sqrt();
+        assert_corners(verts, [
+            Vec3::new(0.4, 0.0, half_diagonal),
+            Vec3::new(0.4, half_diagonal, 0.0),
+            Vec3::new(0.4, -half_diagonal, 0.0),
+            Vec3::new(0.4, 0.0, -half_diagonal),
+        ]);
+        assert_eq!(normal, Vec3::X);
+    }
+
+    #[test]
+    fn rotation_scales_with_size(){
+        //ARRANGE
+        let size = Vec2::new(2.0, 1.0);
+
+        //ACT
+        let (verts, _, _) = create_quad(Side::Forward, size, Vec3::ZERO, 180.0);
+
+        //ASSERT
+        assert_corners(verts, [
+            Vec3::new( 1.0, -0.5, 0.0),
+            Vec3::new(-1.0, -0.5, 0.0),
+            Vec3::new( 1.0,  0.5, 0.0),
+            Vec3::new(-1.0,  0.5, 0.0),
+        ]);
+    }
+
+    #[test]
+    fn faces_sit_on_the_matching_side_of_the_bound(){
+        //ARRANGE
+        let bound = Bound{ min: [-0.5, -0.5, 0.2], max: [0.5, 0.3, 0.4] };
+
+        //ACT
+        let (bottom, _, _) = bound_face(&bound, Side::Down, 0.0);
+        let (left, _, normal) = bound_face(&bound, Side::Left, 0.0);
+
+        //ASSERT
+        assert_corners(bottom, [
+            Vec3::new(-0.5, -0.5, 0.4),
+            Vec3::new( 0.5, -0.5, 0.4),
+            Vec3::new(-0.5, -0.5, 0.2),
+            Vec3::new( 0.5, -0.5, 0.2),
+        ]);
+        assert_corners(left, [
+            Vec3::new(0.5,  0.3, 0.4),
+            Vec3::new(0.5,  0.3, 0.2),
+            Vec3::new(0.5, -0.5, 0.4),
+            Vec3::new(0.5, -0.5, 0.2),
+        ]);
+        assert_eq!(normal, Vec3::X);
+    }
+}
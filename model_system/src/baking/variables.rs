@@ -0,0 +1,62 @@
+use crate::loading::error::VariableError;
+
+pub fn variable_index(vars: &[(String, String)], name: &str) -> Option<usize>{
+    vars.iter().position(|(key, _)| key == name)
+}
+
+///
+/// Follows `#name` references from the variable at `start` to the texture path they end at.
+pub fn resolve_variable(vars: &[(String, String)], start: usize) -> Result<&str, VariableError>{
+    let mut chain = Vec::new();
+    let mut i = start;
+    loop{
+        let (key, value) = &vars[i];
+        let seen = chain.contains(key);
+        chain.push(key.clone());
+        if seen{
+            return Err(VariableError::Cyclic(chain));
+        }
+
+        let Some(reference) = value.strip_prefix('#') else{
+            return Ok(value);
+        };
+        i = variable_index(vars, reference).ok_or_else(|| VariableError::Unknown(reference.to_owned()))?;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::loading::error::VariableError;
+
+    use super::resolve_variable;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)>{
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn references_resolve_to_texture(){
+        //ARRANGE
+        let vars = vars(&[("side", "#main"), ("main", "#stone"), ("stone", "textures/stone.png")]);
+
+        //ACT
+        let texture = resolve_variable(&vars, 0);
+
+        //ASSERT
+        assert_eq!(texture, Ok("textures/stone.png"));
+    }
+
+    #[test]
+    fn cyclic_references_are_reported(){
+        //ARRANGE
+        let vars = vars(&[("a", "#b"), ("b", "#a"), ("c", "#missing")]);
+
+        //ACT
+        let cyclic = resolve_variable(&vars, 0);
+        let unknown = resolve_variable(&vars, 2);
+
+        //ASSERT
+        assert_eq!(cyclic, Err(VariableError::Cyclic(vec!["a".to_owned(), "b".to_owned(), "a".to_owned()])));
+        assert_eq!(unknown, Err(VariableError::Unknown("missing".to_owned())));
+    }
+}
@@ -1,83 +1,315 @@
+use std::collections::BTreeMap;
+
+use glam::Vec3;
 use serde::Deserialize;
 
-#[derive(Deserialize, PartialEq)]
+///
+/// One model file, holding the models of a material for the tile shapes it lists,
+/// and the model of a building or creature.
+/// Paths are relative to the asset root, or to the file itself when they start with "./" or "../".
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ModelFile<'a>{
+    /// Textures, flags and models this file doesn't set itself are taken from this file
     pub inherits: Option<&'a str>,
-    #[serde(borrow)]
-    pub shape_kinds: ShapeKinds<'a>
+    /// Texture variables for every entry in the file, a "#name" value refers to another variable
+    #[serde(default, borrow)]
+    pub textures: BTreeMap<&'a str, &'a str>,
+    /// Multiplies the DF material colour into the textures
+    pub tint: Option<bool>,
+    /// Alpha blended instead of cut out
+    pub transparent: Option<bool>,
+    #[serde(default, borrow)]
+    pub shape_kinds: ShapeKinds<Option<ShapeEntry<'a>>>,
+    /// Model of things without a tile shape, like buildings and creatures
+    #[serde(default, borrow)]
+    pub model: Option<ShapeEntry<'a>>,
 }
 
-#[derive(Deserialize, PartialEq)]
-pub struct ShapeKinds<'a>{
-    #[serde(borrow)]
-    pub wall: Option<ShapeEntry<'a>>,
-    #[serde(borrow)]
-    pub floor: Option<ShapeEntry<'a>>
+macro_rules! shapes {
+    ($($field: ident => $shape: ident,)*) => {
+        ///
+        /// The tile shapes a material can have a model for.
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+        pub enum Shape{
+            $($shape,)*
+        }
+
+        impl Shape{
+            pub const ALL: [Shape; 21] = [$(Shape::$shape,)*];
+
+            ///
+            /// Key of the shape in `shape_kinds`.
+            pub fn name(self) -> &'static str{
+                match self{
+                    $(Shape::$shape => stringify!($field),)*
+                }
+            }
+        }
+
+        ///
+        /// Something for every tile shape.
+        #[derive(Deserialize, PartialEq, Debug, Default, Clone)]
+        #[serde(deny_unknown_fields)]
+        pub struct ShapeKinds<T>{
+            $(
+                #[serde(default)]
+                pub $field: T,
+            )*
+        }
+
+        impl<T> ShapeKinds<T>{
+            pub fn get(&self, shape: Shape) -> &T{
+                match shape{
+                    $(Shape::$shape => &self.$field,)*
+                }
+            }
+
+            pub fn get_mut(&mut self, shape: Shape) -> &mut T{
+                match shape{
+                    $(Shape::$shape => &mut self.$field,)*
+                }
+            }
+        }
+    };
+}
+
+shapes!{
+    no_shape => NoShape,
+    empty => Empty,
+    floor => Floor,
+    boulder => Boulder,
+    pebbles => Pebbles,
+    wall => Wall,
+    fortification => Fortification,
+    up_stair => UpStair,
+    down_stair => DownStair,
+    up_down_stair => UpDownStair,
+    ramp => Ramp,
+    ramp_top => RampTop,
+    brook_bed => BrookBed,
+    brook_top => BrookTop,
+    tree => Tree,
+    sapling => Sapling,
+    shrub => Shrub,
+    endless_pit => EndlessPit,
+    branch => Branch,
+    trunk_branch => TrunkBranch,
+    twig => Twig,
 }
 
-#[derive(Deserialize, PartialEq)]
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ShapeEntry<'a>{
+    #[serde(default)]
     pub visibility: VisibilityDefinition,
-    #[serde(borrow)]
-    pub model: Vec<ModelEntry<'a>>
+    /// Texture variables of this entry, they win over the ones of the file
+    #[serde(default, borrow)]
+    pub textures: BTreeMap<&'a str, &'a str>,
+    pub tint: Option<bool>,
+    pub transparent: Option<bool>,
+    /// Left out to use the model the inherited file has for the same shape
+    #[serde(default, borrow)]
+    pub model: Option<Vec<ModelEntry<'a>>>,
 }
 
-#[derive(Deserialize, PartialEq, Default)]
+///
+/// How much of the neighbouring tiles a model hides, by side.
+/// The most specific setting wins, `sides` covers the four horizontal sides.
+#[derive(Deserialize, PartialEq, Debug, Default, Clone, Copy)]
+#[serde(deny_unknown_fields)]
 pub struct VisibilityDefinition{
     pub all: Option<Visibility>,
     pub sides: Option<Visibility>,
     /// y+
-    /// 
+    ///
     pub top: Option<Visibility>,
     /// y-
-    /// 
-    pub bottom: Option<Visibility>, 
+    ///
+    pub bottom: Option<Visibility>,
     /// z+
-    /// 
+    ///
     pub front: Option<Visibility>,
     /// z-
-    /// 
+    ///
     pub back: Option<Visibility>,
     /// x+
-    /// 
+    ///
     pub left: Option<Visibility>,
-    /// x+
-    /// 
+    /// x-
+    ///
     pub right: Option<Visibility>,
 }
 
-#[derive(Deserialize, PartialEq)]
+impl VisibilityDefinition{
+    ///
+    /// Settings of `self`, with the ones it leaves out taken from `parent`.
+    pub fn or(self, parent: Self) -> Self{
+        Self{
+            all: self.all.or(parent.all),
+            sides: self.sides.or(parent.sides),
+            top: self.top.or(parent.top),
+            bottom: self.bottom.or(parent.bottom),
+            front: self.front.or(parent.front),
+            back: self.back.or(parent.back),
+            left: self.left.or(parent.left),
+            right: self.right.or(parent.right),
+        }
+    }
+
+    ///
+    /// Sides nothing is said about don't hide anything.
+    pub fn get(&self, side: Side) -> Visibility{
+        self.specified(side).unwrap_or(Visibility::Transparent)
+    }
+
+    ///
+    /// The setting for `side`, None when nothing is said about it.
+    pub fn specified(&self, side: Side) -> Option<Visibility>{
+        let specific = match side{
+            Side::Up => self.top,
+            Side::Down => self.bottom,
+            Side::Left => self.left.or(self.sides),
+            Side::Right => self.right.or(self.sides),
+            Side::Forward => self.front.or(self.sides),
+            Side::Backwards => self.back.or(self.sides),
+        };
+        specific.or(self.all)
+    }
+}
+
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
 pub enum Visibility{
     Solid,
     Transparent
 }
 
-#[derive(Deserialize, PartialEq)]
+///
+/// Sides of a tile, named after the neighbour they face.
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+pub enum Side{
+    /// y+
+    Up,
+    /// y-
+    Down,
+    /// x+
+    Left,
+    /// x-
+    Right,
+    /// z+
+    Forward,
+    /// z-
+    Backwards,
+}
+
+impl Side{
+    pub const ALL: [Side; 6] = [Side::Up, Side::Down, Side::Left, Side::Right, Side::Forward, Side::Backwards];
+
+    ///
+    /// (u axis, normal, v axis) of a face looking towards this side.
+    pub fn axes(self) -> [Vec3; 3]{
+        match self{
+            Side::Up => [Vec3::X, Vec3::Y, Vec3::Z],
+            Side::Down => [Vec3::X, Vec3::NEG_Y, Vec3::NEG_Z],
+            Side::Left => [Vec3::NEG_Z, Vec3::X, Vec3::NEG_Y],
+            Side::Right => [Vec3::Z, Vec3::NEG_X, Vec3::NEG_Y],
+            Side::Forward => [Vec3::X, Vec3::Z, Vec3::NEG_Y],
+            Side::Backwards => [Vec3::NEG_X, Vec3::NEG_Z, Vec3::NEG_Y],
+        }
+    }
+}
+
+///
+/// When a model part is left out, depending on whether the neighbour at a side hides it.
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum Cull{
+    Never,
+    WhenVisible(Side),
+    WhenHidden(Side),
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct ModelEntry<'a>{
+    /// The whole tile when left out
+    #[serde(default)]
     pub bound: Bound,
 
     #[serde(borrow)]
     pub definition: ModelDefinition<'a>,
 
     #[serde(borrow)]
-    pub coloring: Coloring<'a>
+    pub coloring: Coloring<'a>,
+
+    /// Left out the part is always drawn, except for the faces of a `Solid` on the border of the tile,
+    /// which are left out when the neighbour covers them
+    pub cull: Option<Cull>,
 }
 
-#[derive(Deserialize, PartialEq)]
+///
+/// Box in tile coordinates, the tile spans -0.5 to 0.5 on every axis.
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
 pub struct Bound{
     pub min: [f32;3],
     pub max: [f32;3],
 }
 
-#[derive(Deserialize, PartialEq)]
+impl Default for Bound{
+    fn default() -> Self {
+        Self{
+            min: [-0.5; 3],
+            max: [0.5; 3],
+        }
+    }
+}
+
+impl Bound{
+    pub fn center(&self) -> Vec3{
+        (Vec3::from(self.min) + Vec3::from(self.max)) / 2.0
+    }
+
+    pub fn size(&self) -> Vec3{
+        Vec3::from(self.max) - Vec3::from(self.min)
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
 pub enum ModelDefinition<'a>{
+    /// A signed distance field expression like "round(aabb([-0.3, -0.5, -0.3], [0.3, 0.1, 0.3]), 0.1)",
+    /// in tile coordinates and cut off at the bound, see [`crate::sdf::parse`]
     SDF(&'a str),
-    Solid
+    /// A box filling the bound
+    Solid,
+    /// A quad on the `side` of the bound, facing that side
+    Face{
+        side: Side,
+        /// Degrees around the face normal
+        #[serde(default)]
+        rotation: f32,
+    },
+    /// An OBJ or glTF file, scaled from the tile into the bound
+    Mesh{
+        src: &'a str,
+        /// Colouring of the faces using a material of the mesh file, instead of the texture embedded in it or the entry colouring
+        #[serde(default, borrow)]
+        materials: BTreeMap<&'a str, Coloring<'a>>,
+    },
+    /// Triangles written out in the file, scaled from the tile into the bound
+    Triangles{
+        verts: Vec<[f32;3]>,
+        uvs: Vec<[f32;2]>,
+        normals: Option<Vec<[f32;3]>>,
+        indices: Vec<[u16;3]>,
+    },
 }
 
-#[derive(Deserialize, PartialEq)]
+#[derive(Deserialize, PartialEq, Debug)]
 pub enum Coloring<'a>{
     UvMapped{
-        texture: &'a str
+        /// Name of a texture variable
+        texture: &'a str,
+        /// (x, y, width, height) in pixels of the texture, for tilesets holding many tiles
+        clip: Option<[u32;4]>,
     }
 }
@@ -1,3 +1,6 @@
-mod format;
-mod loading;
-mod naming;
+pub mod baking;
+pub mod format;
+pub mod loading;
+pub mod naming;
+pub mod paths;
+pub mod sdf;
@@ -0,0 +1,141 @@
+use std::{path::{Path, PathBuf}, fmt::Display, error::Error};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VariableError{
+    /// A `#name` pointing at a variable that doesn't exist
+    Unknown(String),
+    /// The keys visited, ending with the one seen twice
+    Cyclic(Vec<String>),
+}
+
+impl Display for VariableError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            VariableError::Unknown(name) => write!(f, "unknown texture variable {}", name),
+            VariableError::Cyclic(chain) => write!(f, "cyclic reference {}", chain.join(" -> #")),
+        }
+    }
+}
+
+///
+/// 1-based position in a model file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Span{
+    pub line: usize,
+    pub column: usize,
+}
+
+///
+/// Position of `part` in `source`, `part` has to be borrowed from `source`.
+pub fn span_of(source: &str, part: &str) -> Span{
+    let offset = (part.as_ptr() as usize)
+        .saturating_sub(source.as_ptr() as usize)
+        .min(source.len());
+    let before = &source.as_bytes()[..offset];
+    let line_start = before.iter().rposition(|x| *x == b'\n').map_or(0, |x| x + 1);
+
+    Span{
+        line: before.iter().filter(|x| **x == b'\n').count() + 1,
+        column: offset - line_start + 1,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorCause{
+    Io(String),
+    Parse(String),
+    NotAModelFile,
+    DuplicateIdentifier{
+        identifier: String,
+        other: PathBuf,
+    },
+    Variable(VariableError),
+    MissingTextureFile(String),
+    MeshImport(String),
+    UnusedMaterial{
+        src: String,
+        material: String,
+    },
+    MissingInherit(PathBuf),
+    CyclicInherit(PathBuf),
+    BrokenInherit(PathBuf),
+    /// The shape has an entry without a model, and the inherited files have no model for it either
+    MissingModel(&'static str),
+    OutsideAssetRoot(String),
+    InvalidBound,
+    MissingNormals,
+    Sdf(String),
+}
+
+impl Display for ErrorCause{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            ErrorCause::Io(err) => write!(f, "can't read file: {}", err),
+            ErrorCause::Parse(err) => write!(f, "{}", err),
+            ErrorCause::NotAModelFile => write!(f, "not a model file, model files end in .json"),
+            ErrorCause::DuplicateIdentifier{ identifier, other } => write!(f, "duplicate identifier {}, also defined by {}", identifier, other.display()),
+            ErrorCause::Variable(err) => write!(f, "{}", err),
+            ErrorCause::MissingTextureFile(texture) => write!(f, "missing texture file {}", texture),
+            ErrorCause::MeshImport(err) => write!(f, "unreadable mesh import: {}", err),
+            ErrorCause::UnusedMaterial{ src, material } => write!(f, "{} has no faces using material {}", src, material),
+            ErrorCause::MissingInherit(target) => write!(f, "missing inherits target {}", target.display()),
+            ErrorCause::CyclicInherit(target) => write!(f, "cyclic inherits of {}", target.display()),
+            ErrorCause::BrokenInherit(target) => write!(f, "inherits target {} is broken", target.display()),
+            ErrorCause::MissingModel(shape) => write!(f, "{} has no model here or in the inherited files", shape),
+            ErrorCause::OutsideAssetRoot(src) => write!(f, "{} points outside of the asset folder", src),
+            ErrorCause::InvalidBound => write!(f, "bound min is above its max"),
+            ErrorCause::MissingNormals => write!(f, "Triangles without normals"),
+            ErrorCause::Sdf(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+///
+/// A problem in a model file, `span` points at the element causing it when it is known.
+/// `path` is the file on disk, so it is clear which asset pack is broken.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ModelError{
+    pub path: PathBuf,
+    pub span: Option<Span>,
+    pub cause: ErrorCause,
+}
+
+impl ModelError{
+    pub fn new(path: &Path, span: Option<Span>, cause: ErrorCause) -> Self{
+        Self{
+            path: path.to_owned(),
+            span,
+            cause,
+        }
+    }
+}
+
+impl Display for ModelError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.span{
+            Some(span) => write!(f, "{}:{}:{}: {}", self.path.display(), span.line, span.column, self.cause),
+            None => write!(f, "{}: {}", self.path.display(), self.cause),
+        }
+    }
+}
+
+impl Error for ModelError{}
+
+#[cfg(test)]
+mod tests{
+    use super::{span_of, Span};
+
+    #[test]
+    fn span_points_at_borrowed_part(){
+        //ARRANGE
+        let source = "{\n    \"inherits\": \"../mod.json\",\n}";
+        let start = source.find("..").unwrap();
+        let part = &source[start..start + 2];
+
+        //ACT
+        let span = span_of(source, part);
+
+        //ASSERT
+        assert_eq!(span, Span{ line: 2, column: 18 });
+    }
+}
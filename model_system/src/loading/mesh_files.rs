@@ -0,0 +1,551 @@
+use std::{path::{PathBuf, Path}, fmt::{Debug, Display}, fs::File, error::Error, io::{BufReader, BufRead}, collections::{HashMap, HashSet}};
+
+use glam::{Vec3, Vec2, Mat4, Mat3};
+use gltf::{mesh::Mode, Gltf, Node};
+
+///
+/// Faces of a mesh file sharing a material, with their own vertices and indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshGroup{
+    /// Set by `usemtl` or the glTF material, None for faces without a material.
+    pub material: Option<String>,
+    /// Label of the base colour texture embedded in the mesh file, as the asset server names it.
+    pub texture: Option<String>,
+    pub verts: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub normals: Vec<Vec3>,
+    /// Linear rgba multiplied into the model colour.
+    pub colors: Option<Vec<[f32; 4]>>,
+    pub indices: Vec<u16>,
+}
+
+impl MeshGroup{
+    fn new(material: Option<String>) -> Self{
+        Self{
+            material,
+            texture: None,
+            verts: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            colors: None,
+            indices: Vec::new(),
+        }
+    }
+}
+
+pub fn load_mesh_file(path: &str) -> Result<Vec<MeshGroup>, Box<dyn Error>>{
+    let path_buf = PathBuf::from(path);
+
+    match path_buf.extension(){
+        Some(extension) => match extension.to_string_lossy().as_ref() {
+            "obj" => load_wavefront_file(&path_buf),
+            "gltf" | "glb" => load_gltf_file(&path_buf),
+            x => MeshLoadingError::new(path, format!("Invalid extension: {}",x)).into_boxed_err()
+        },
+        None => MeshLoadingError::new(path, "No extension found on path").into_boxed_err()
+    }
+}
+
+///
+/// Reads an OBJ file and checks that every `usemtl` names a material declared by one of its `mtllib` files.
+/// Textures are not taken from the MTL files, exporters tend to write paths from the artist's machine,
+/// instead `Mesh` entries bind colourings to the material names.
+fn load_wavefront_file(path: &Path) -> Result<Vec<MeshGroup>, Box<dyn Error>>{
+    let file = File::open(path)?;
+    let wavefront = parse_wavefront(BufReader::new(file), path)?;
+
+    if wavefront.libraries.is_empty(){
+        return Ok(wavefront.groups);
+    }
+
+    let folder = path.parent().unwrap_or(Path::new(""));
+    let mut declared = HashSet::new();
+    for library in &wavefront.libraries{
+        let library = folder.join(library);
+        let file = File::open(&library)
+            .map_err(|err| MeshLoadingError::new(path.display(), format!("can't read material library {}: {}", library.display(), err)))?;
+        declared.extend(parse_material_names(BufReader::new(file))?);
+    }
+
+    for (material, line) in &wavefront.used{
+        if !declared.contains(material){
+            return MeshLoadingError::new(path.display(), format!("line {}: material {} is not declared by {}", line, material, wavefront.libraries.join(", "))).into_boxed_err();
+        }
+    }
+
+    Ok(wavefront.groups)
+}
+
+struct Wavefront{
+    groups: Vec<MeshGroup>,
+    /// `mtllib` files, relative to the OBJ file
+    libraries: Vec<String>,
+    /// Materials picked by `usemtl`, with the line first picking them
+    used: Vec<(String, usize)>,
+}
+
+///
+/// Key a vertex is deduplicated on, normals generated for a face are keyed by their bits.
+#[derive(PartialEq, Eq, Hash)]
+struct VertexKey{
+    vert: usize,
+    uv: Option<usize>,
+    normal: Result<usize, [u32; 3]>,
+}
+
+///
+/// Lines joined by a trailing '\' with comments removed, numbered by the line they start on.
+fn logical_lines(reader: impl BufRead) -> impl Iterator<Item = std::io::Result<(usize, String)>>{
+    let mut lines = reader.lines().enumerate();
+
+    std::iter::from_fn(move ||{
+        let (number, line) = lines.next()?;
+        let mut line = match line{
+            Ok(x) => x,
+            Err(err) => return Some(Err(err)),
+        };
+
+        while line.trim_end().ends_with('\\'){
+            let trimmed = line.trim_end().len() - 1;
+            line.truncate(trimmed);
+            match lines.next(){
+                Some((_, Ok(next))) => {
+                    line.push(' ');
+                    line.push_str(&next);
+                },
+                Some((_, Err(err))) => return Some(Err(err)),
+                None => break,
+            }
+        }
+
+        if let Some(comment) = line.find('#'){
+            line.truncate(comment);
+        }
+        Some(Ok((number + 1, line)))
+    })
+}
+
+///
+/// OBJ index to a position in `len` elements, negative indices count back from the last element read.
+fn resolve_index(token: &str, len: usize, kind: &str) -> Result<usize, String>{
+    let index: i64 = token.parse().map_err(|err| format!("invalid {} index {}: {}", kind, token, err))?;
+
+    let resolved = if index > 0{
+        index - 1
+    }
+    else if index < 0{
+        len as i64 + index
+    }
+    else{
+        return Err(format!("{} index 0, indices start at 1", kind));
+    };
+
+    if resolved < 0 || resolved >= len as i64{
+        return Err(format!("{} index {} out of range, {} read so far", kind, token, len));
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_floats<const N: usize>(tokens: &[&str], required: usize) -> Result<[f32; N], String>{
+    if tokens.len() < required{
+        return Err(format!("expected at least {} numbers, found {}", required, tokens.len()));
+    }
+
+    let mut out = [0.0; N];
+    for (i, token) in tokens.iter().take(N).enumerate(){
+        out[i] = token.parse().map_err(|err| format!("invalid number {}: {}", token, err))?;
+    }
+    Ok(out)
+}
+
+///
+/// Normal of a polygon by Newell's method, works for concave and slightly non planar polygons too.
+fn polygon_normal(points: &[Vec3]) -> Vec3{
+    let mut normal = Vec3::ZERO;
+    for (i, a) in points.iter().enumerate(){
+        let b = points[(i + 1) % points.len()];
+        normal += Vec3::new(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y),
+        );
+    }
+    normal.normalize_or_zero()
+}
+
+fn parse_wavefront(reader: impl BufRead, path: &Path) -> Result<Wavefront, Box<dyn Error>>{
+    let mut raw_verts = Vec::new();
+    let mut raw_uvs = Vec::new();
+    let mut raw_normals = Vec::new();
+
+    let mut groups = vec![MeshGroup::new(None)];
+    let mut dedup: Vec<HashMap<VertexKey, u16>> = vec![HashMap::new()];
+    let mut current = 0;
+
+    let mut libraries = Vec::new();
+    let mut used: Vec<(String, usize)> = Vec::new();
+
+    for line in logical_lines(reader){
+        let (number, line) = line?;
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        let Some((&type_, args)) = tokens.split_first() else{
+            continue;
+        };
+
+        let result: Result<(), String> = (||{
+            match type_ {
+                "v" => {
+                    let [x, y, z, w] = parse_floats::<4>(args, 3)?;
+                    let w = if args.len() > 3 { w } else { 1.0 };
+                    raw_verts.push(Vec3::new(x, y, z) / w);
+                },
+                "vt" => {
+                    let [u, v] = parse_floats::<2>(args, 1)?;
+                    raw_uvs.push(Vec2::new(u, v));
+                },
+                "vn" => {
+                    let [x, y, z] = parse_floats::<3>(args, 3)?;
+                    raw_normals.push(Vec3::new(x, y, z));
+                },
+                "f" => {
+                    if args.len() < 3{
+                        return Err(format!("face with {} vertices, at least 3 are needed", args.len()));
+                    }
+
+                    let mut corners = Vec::new();
+                    for corner in args{
+                        let mut parts = corner.split('/');
+                        let vert = resolve_index(parts.next().unwrap_or(""), raw_verts.len(), "vertex")?;
+                        let uv = match parts.next(){
+                            Some(x) if !x.is_empty() => Some(resolve_index(x, raw_uvs.len(), "texture")?),
+                            _ => None,
+                        };
+                        let normal = match parts.next(){
+                            Some(x) if !x.is_empty() => Some(resolve_index(x, raw_normals.len(), "normal")?),
+                            _ => None,
+                        };
+                        corners.push((vert, uv, normal));
+                    }
+
+                    let flat = polygon_normal(&corners.iter().map(|x| raw_verts[x.0]).collect::<Vec<_>>());
+
+                    let group = &mut groups[current];
+                    let mut indices = Vec::new();
+                    for (vert, uv, normal) in corners{
+                        let key = VertexKey{
+                            vert,
+                            uv,
+                            normal: normal.ok_or(flat.to_array().map(f32::to_bits)),
+                        };
+
+                        let index = match dedup[current].get(&key){
+                            Some(index) => *index,
+                            None => {
+                                let index = u16::try_from(group.verts.len())
+                                    .map_err(|_| format!("more than {} vertices using one material", u16::MAX as usize + 1))?;
+                                group.verts.push(raw_verts[vert]);
+                                group.uvs.push(uv.map_or(Vec2::ZERO, |x| raw_uvs[x]));
+                                group.normals.push(normal.map_or(flat, |x| raw_normals[x]));
+                                dedup[current].insert(key, index);
+                                index
+                            },
+                        };
+                        indices.push(index);
+                    }
+
+                    //Fan triangulation, keeps the winding of the polygon
+                    for i in 1..indices.len() - 1{
+                        group.indices.extend([indices[0], indices[i], indices[i + 1]]);
+                    }
+                },
+                "usemtl" => {
+                    let material = args.join(" ");
+                    if material.is_empty(){
+                        return Err("usemtl without a material name".to_owned());
+                    }
+
+                    current = match groups.iter().position(|x| x.material.as_ref() == Some(&material)){
+                        Some(x) => x,
+                        None => {
+                            used.push((material.clone(), number));
+                            groups.push(MeshGroup::new(Some(material)));
+                            dedup.push(HashMap::new());
+                            groups.len() - 1
+                        },
+                    };
+                },
+                "mtllib" => {
+                    libraries.extend(args.iter().map(|x| x.to_string()));
+                },
+                //Object names, groups, smoothing groups and statements for curves or other renderers don't change the imported mesh
+                _ => {},
+            }
+            Ok(())
+        })();
+
+        if let Err(err) = result{
+            return MeshLoadingError::new(path.display(), format!("line {}: {}", number, err)).into_boxed_err();
+        }
+    }
+
+    groups.retain(|x| !x.indices.is_empty());
+    Ok(Wavefront{ groups, libraries, used })
+}
+
+fn parse_material_names(reader: impl BufRead) -> Result<Vec<String>, Box<dyn Error>>{
+    let mut names = Vec::new();
+    for line in logical_lines(reader){
+        let (_, line) = line?;
+        let mut tokens = line.split_whitespace();
+        if tokens.next() == Some("newmtl"){
+            names.push(tokens.collect::<Vec<_>>().join(" "));
+        }
+    }
+    Ok(names)
+}
+
+
+///
+/// Reads every primitive of the default scene of a glTF file as its own group, with the node transforms applied.
+/// Embedded textures are left to the asset server, groups only name them by the label it gives them.
+fn load_gltf_file(path: &Path) -> Result<Vec<MeshGroup>, Box<dyn Error>>{
+    let gltf = Gltf::open(path)?;
+    let folder = path.parent().unwrap_or(Path::new(""));
+    let buffers = gltf::import_buffers(&gltf.document, Some(folder), gltf.blob.clone())?;
+
+    let mut groups = Vec::new();
+    match gltf.document.default_scene().or_else(|| gltf.document.scenes().next()){
+        Some(scene) => {
+            for node in scene.nodes(){
+                read_gltf_node(path, &node, Mat4::IDENTITY, &buffers, &mut groups)?;
+            }
+        },
+        None => {
+            for mesh in gltf.document.meshes(){
+                read_gltf_mesh(path, &mesh, Mat4::IDENTITY, &buffers, &mut groups)?;
+            }
+        },
+    }
+
+    Ok(groups)
+}
+
+fn read_gltf_node(path: &Path, node: &Node, parent: Mat4, buffers: &[gltf::buffer::Data], groups: &mut Vec<MeshGroup>) -> Result<(), Box<dyn Error>>{
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+    if let Some(mesh) = node.mesh(){
+        read_gltf_mesh(path, &mesh, transform, buffers, groups)?;
+    }
+    for child in node.children(){
+        read_gltf_node(path, &child, transform, buffers, groups)?;
+    }
+    Ok(())
+}
+
+fn read_gltf_mesh(path: &Path, mesh: &gltf::Mesh, transform: Mat4, buffers: &[gltf::buffer::Data], groups: &mut Vec<MeshGroup>) -> Result<(), Box<dyn Error>>{
+    let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+    //Mirroring transforms turn the faces inside out
+    let mirrored = transform.determinant() < 0.0;
+    let name = mesh.name().map(|x| x.to_owned()).unwrap_or_else(|| format!("mesh {}", mesh.index()));
+
+    for primitive in mesh.primitives(){
+        let error = |err: String| MeshLoadingError::new(path.display(), format!("{} primitive {}: {}", name, primitive.index(), err));
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|x| &x.0[..]));
+
+        let Some(positions) = reader.read_positions() else{
+            return error("no positions".to_owned()).into_boxed_err();
+        };
+        let verts = positions.map(|x| transform.transform_point3(Vec3::from(x))).collect::<Vec<_>>();
+        let uvs = match reader.read_tex_coords(0){
+            Some(uvs) => uvs.into_f32().map(Vec2::from).collect(),
+            None => vec![Vec2::ZERO; verts.len()],
+        };
+        let normals = reader.read_normals()
+            .map(|x| x.map(|x| (normal_matrix * Vec3::from(x)).normalize_or_zero()).collect::<Vec<_>>());
+        let colors = reader.read_colors(0).map(|x| x.into_rgba_f32().collect::<Vec<_>>());
+        let indices = match reader.read_indices(){
+            Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+            None => (0..verts.len() as u32).collect(),
+        };
+
+        if let Some(index) = indices.iter().find(|x| **x as usize >= verts.len()){
+            return error(format!("index {} out of range, {} vertices", index, verts.len())).into_boxed_err();
+        }
+
+        let mut triangles = match primitive.mode(){
+            Mode::Triangles => indices.chunks_exact(3).map(|x| [x[0], x[1], x[2]]).collect::<Vec<_>>(),
+            //Every other triangle of a strip is wound the other way
+            Mode::TriangleStrip => indices.windows(3).enumerate()
+                .map(|(i, x)| if i % 2 == 0 { [x[0], x[1], x[2]] } else { [x[1], x[0], x[2]] })
+                .collect(),
+            Mode::TriangleFan => indices.windows(2).skip(1).map(|x| [indices[0], x[0], x[1]]).collect(),
+            mode => return error(format!("{:?} primitives can't be imported", mode)).into_boxed_err(),
+        };
+        if mirrored{
+            triangles.iter_mut().for_each(|x| x.swap(1, 2));
+        }
+
+        let material = primitive.material();
+        let mut group = MeshGroup::new(material.name().map(|x| x.to_owned()));
+        //Same label bevy's glTF loader gives the texture
+        group.texture = material.pbr_metallic_roughness().base_color_texture().map(|x| format!("Texture{}", x.texture().index()));
+
+        match normals{
+            Some(normals) => {
+                group.verts = verts;
+                group.uvs = uvs;
+                group.normals = normals;
+                group.colors = colors;
+                group.indices = triangles.into_iter().flatten()
+                    .map(|x| x as u16)
+                    .collect();
+                if group.verts.len() > u16::MAX as usize + 1{
+                    return error(format!("{} vertices, at most {} are supported", group.verts.len(), u16::MAX as usize + 1)).into_boxed_err();
+                }
+            },
+            None => {
+                //Flat normals need their own vertices for every triangle
+                if triangles.len() * 3 > u16::MAX as usize + 1{
+                    return error(format!("{} triangles without normals, at most {} are supported", triangles.len(), (u16::MAX as usize + 1) / 3)).into_boxed_err();
+                }
+                let mut flat_colors = Vec::new();
+                for triangle in triangles{
+                    let normal = polygon_normal(&triangle.map(|x| verts[x as usize]));
+                    for x in triangle{
+                        group.indices.push(group.verts.len() as u16);
+                        group.verts.push(verts[x as usize]);
+                        group.uvs.push(uvs.get(x as usize).copied().unwrap_or(Vec2::ZERO));
+                        group.normals.push(normal);
+                        if let Some(colors) = &colors{
+                            flat_colors.push(colors.get(x as usize).copied().unwrap_or([1.0; 4]));
+                        }
+                    }
+                }
+                group.colors = colors.map(|_| flat_colors);
+            },
+        }
+
+        if group.uvs.len() != group.verts.len() || group.normals.len() != group.verts.len() || matches!(&group.colors, Some(x) if x.len() != group.verts.len()){
+            return error("attributes have different lengths".to_owned()).into_boxed_err();
+        }
+
+        if !group.indices.is_empty(){
+            groups.push(group);
+        }
+    }
+    Ok(())
+}
+
+pub struct MeshLoadingError{
+    path: String,
+    error: String,
+}
+
+impl MeshLoadingError{
+    pub fn new(path: impl ToString, error: impl ToString) -> Self{
+        Self{
+            error: error.to_string(),
+            path: path.to_string()
+        }
+    }
+
+    pub fn into_boxed_err<T>(self) -> Result<T,Box<dyn Error>>{
+        Err(Box::new(self))
+    }
+}
+
+impl Debug for MeshLoadingError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+        f.write_str("Error while loading mesh at (")?;
+        std::fmt::Debug::fmt(&self.path, f)?;
+        f.write_str("): ")?;
+        std::fmt::Debug::fmt(&self.error, f)?;
+        Ok(())
+    }
+}
+
+impl Display for MeshLoadingError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Error while loading mesh at (")?;
+        std::fmt::Display::fmt(&self.path, f)?;
+        f.write_str("): ")?;
+        std::fmt::Display::fmt(&self.error, f)?;
+        Ok(())
+    }
+}
+
+impl Error for MeshLoadingError{}
+
+#[cfg(test)]
+mod tests{
+    use std::path::Path;
+
+    use glam::{Vec2, Vec3};
+
+    use super::{load_gltf_file, parse_wavefront};
+
+    #[test]
+    fn polygons_without_uvs_or_normals_are_fanned_with_flat_normals(){
+        //ARRANGE
+        let source = "v 0 0 0\nv  1 0 0\nv 1 1 0\n\tv 0 1 0 # last corner\nf 1 2 3 4\nf -4 -2 -1\n";
+
+        //ACT
+        let wavefront = parse_wavefront(source.as_bytes(), Path::new("quad.obj")).unwrap();
+
+        //ASSERT
+        let group = &wavefront.groups[0];
+        assert_eq!(wavefront.groups.len(), 1);
+        assert_eq!(group.verts.len(), 4);
+        assert_eq!(group.indices, vec![0, 1, 2, 0, 2, 3, 0, 2, 3]);
+        assert!(group.normals.iter().all(|x| *x == Vec3::Z));
+        assert!(group.uvs.iter().all(|x| *x == Vec2::ZERO));
+    }
+
+    #[test]
+    fn usemtl_splits_faces_into_groups(){
+        //ARRANGE
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nusemtl stone\nf 1/1/1 2/1/1 3/1/1\nusemtl wood\nf 1//1 3//1 2//1\nusemtl stone\nf 3/1 2/1 1/1\n";
+
+        //ACT
+        let wavefront = parse_wavefront(source.as_bytes(), Path::new("mixed.obj")).unwrap();
+
+        //ASSERT
+        let materials = wavefront.groups.iter().map(|x| x.material.as_deref()).collect::<Vec<_>>();
+        assert_eq!(materials, vec![Some("stone"), Some("wood")]);
+        assert_eq!(wavefront.groups[0].indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(wavefront.groups[1].indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn bad_indices_report_their_line(){
+        //ARRANGE
+        let source = "v 0 0 0\nv 1 0 0\nf 1 2 3\n";
+
+        //ACT
+        let err = parse_wavefront(source.as_bytes(), Path::new("broken.obj")).err().unwrap();
+
+        //ASSERT
+        assert!(err.to_string().contains("line 3: vertex index 3 out of range"), "{}", err);
+    }
+
+    #[test]
+    fn gltf_nodes_are_transformed_and_mirrored_faces_turned_back(){
+        //ARRANGE
+        let path = Path::new("./test_files/meshes/mirrored.gltf");
+
+        //ACT
+        let groups = load_gltf_file(path).unwrap();
+
+        //ASSERT
+        let group = &groups[0];
+        assert_eq!(groups.len(), 1);
+        assert_eq!(group.material.as_deref(), Some("paint"));
+        assert_eq!(group.texture.as_deref(), Some("Texture0"));
+        assert_eq!(group.verts, vec![Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 1.0), Vec3::new(-1.0, 0.0, 1.0)]);
+        assert_eq!(group.uvs, vec![Vec2::ZERO, Vec2::Y, Vec2::X]);
+        assert!(group.normals.iter().all(|x| x.abs_diff_eq(Vec3::Z, 0.0001)));
+        assert_eq!(group.colors, Some(vec![[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], [0.0, 1.0, 0.0, 1.0]]));
+        assert_eq!(group.indices, vec![0, 1, 2]);
+    }
+}
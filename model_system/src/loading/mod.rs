@@ -1,8 +1,68 @@
-use self::scanner::Scanner;
+use std::{path::{Path, PathBuf}, fs};
 
+use crate::baking::BakedModels;
+
+use self::{scanner::Scanner, error::ModelError};
+
+pub use self::model_files::{LoadedModels, LoadedFile};
+
+pub mod error;
+pub mod mesh_files;
 mod model_files;
 mod scanner;
 
-pub fn load_models(){
+///
+/// Where model files, meshes and textures are read from, by their path inside the asset tree.
+pub trait AssetTree{
+    ///
+    /// Every file below `folder` as (path inside the tree, file on disk), sorted by path.
+    fn files(&self, folder: &str) -> Vec<(PathBuf, PathBuf)>;
+
+    ///
+    /// File on disk for a path inside the tree.
+    fn find(&self, path: &Path) -> Option<PathBuf>;
+}
+
+///
+/// A single folder on disk as the asset tree.
+pub struct Folder(pub PathBuf);
+
+impl Folder{
+    fn walk(&self, dir: &Path, out: &mut Vec<(PathBuf, PathBuf)>){
+        let Ok(entries) = fs::read_dir(dir) else{
+            return;
+        };
+
+        for entry in entries.flatten(){
+            let file = entry.path();
+            if file.is_dir(){
+                self.walk(&file, out);
+            }
+            else if let Ok(path) = file.strip_prefix(&self.0){
+                out.push((path.to_owned(), file.clone()));
+            }
+        }
+    }
+}
+
+impl AssetTree for Folder{
+    fn files(&self, folder: &str) -> Vec<(PathBuf, PathBuf)> {
+        let mut out = Vec::new();
+        self.walk(&self.0.join(folder), &mut out);
+        out.sort();
+        out
+    }
+
+    fn find(&self, path: &Path) -> Option<PathBuf> {
+        Some(self.0.join(path)).filter(|x| x.is_file())
+    }
+}
+
+///
+/// Loads and bakes every model file below `folder`, along with every problem found on the way.
+/// Broken models are left out, the rest still loads.
+pub fn load_models(tree: &impl AssetTree, folder: &str) -> (BakedModels, Vec<ModelError>){
     let mut scanner = Scanner::new();
-}
\ No newline at end of file
+    scanner.scan(tree, folder);
+    scanner.load().bake(tree)
+}
@@ -1,15 +1,31 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
 
 use crate::{format::ModelFile, naming::MaterialIdentifier};
 
+use super::error::ModelError;
+
+pub struct LoadedFile<'a>{
+    /// File on disk
+    pub file: &'a Path,
+    pub source: &'a str,
+    pub model: Result<ModelFile<'a>, ModelError>,
+}
+
 pub struct LoadedModels<'a>{
-    entries: BTreeMap<&'a MaterialIdentifier, ModelFile<'a>>
+    /// Model files of the scanned folder, as (identifier, path inside the asset tree)
+    pub(crate) entries: &'a [(MaterialIdentifier, PathBuf)],
+    /// Every file read, the scanned ones and the ones they inherit from, by path inside the asset tree
+    pub(crate) files: BTreeMap<&'a Path, LoadedFile<'a>>,
+    /// Found while scanning
+    pub(crate) problems: Vec<ModelError>,
 }
 
 impl<'a> LoadedModels<'a>{
-    pub fn new(entries: BTreeMap<&'a MaterialIdentifier, ModelFile<'a>>) -> Self{
+    pub fn new(entries: &'a [(MaterialIdentifier, PathBuf)], files: BTreeMap<&'a Path, LoadedFile<'a>>, problems: Vec<ModelError>) -> Self{
         Self {
-            entries
+            entries,
+            files,
+            problems,
         }
     }
 }
@@ -21,11 +37,11 @@ impl<'a> LoadedModels<'a>{
     }
 
     pub fn contains<'b>(&self, file: &ModelFile<'b>) -> bool{
-        for x in self.entries.values(){
-            if x.eq(file){
+        for x in self.files.values(){
+            if matches!(&x.model, Ok(x) if x.eq(file)){
                 return true;
             }
         }
         false
     }
-}
\ No newline at end of file
+}
@@ -1,92 +1,153 @@
-use std::{path::{Path, PathBuf}, fs, f32::consts::E, error::Error, io, collections::BTreeMap};
+use std::{path::PathBuf, fs, collections::BTreeMap, ffi::OsStr};
 
-use crate::{format::ModelFile, naming::{Element, MaterialIdentifier}};
+use serde::Deserialize;
 
-use super::model_files::LoadedModels;
+use crate::{format::ModelFile, naming::MaterialIdentifier, paths::resolve};
+
+use super::{model_files::{LoadedModels, LoadedFile}, error::{ModelError, ErrorCause, Span}, AssetTree};
+
+///
+/// A model file read before parsing, so the parsed files can borrow from it.
+struct Source{
+    /// File on disk
+    file: PathBuf,
+    content: Result<String, String>,
+}
+
+///
+/// Just enough of a model file to follow its inherits, the rest is checked when it is parsed.
+#[derive(Deserialize)]
+struct Inherits{
+    inherits: Option<String>,
+}
 
 pub struct Scanner{
-    files: Vec<(MaterialIdentifier, String)>,
-    name: Vec<Element>, 
+    entries: Vec<(MaterialIdentifier, PathBuf)>,
+    sources: BTreeMap<PathBuf, Source>,
+    problems: Vec<ModelError>,
 }
 
 impl Scanner{
     pub fn new() -> Self{
         Self{
-            files: Vec::new(),
-            name: Vec::new(),
+            entries: Vec::new(),
+            sources: BTreeMap::new(),
+            problems: Vec::new(),
         }
     }
 
-    pub fn scan_dir(&mut self, dir: &Path) -> Result<(), io::Error>{
-        println!("loading: {:?}",dir);
-        let dir = fs::read_dir(dir)?;
-
-        for x in dir{
-            let entry = x?;
-            let file_type = entry.file_type()?;
-            if file_type.is_dir(){
-                self.name.push(Element::Custom(entry.file_name().to_str().unwrap().to_owned()));
-                self.scan_dir(&entry.path())?;
-                self.name.pop();
+    ///
+    /// Reads every model file below `folder`, and the files they inherit from wherever they are.
+    pub fn scan(&mut self, tree: &impl AssetTree, folder: &str){
+        let mut identifiers: BTreeMap<MaterialIdentifier, PathBuf> = BTreeMap::new();
+
+        for (path, file) in tree.files(folder){
+            if path.extension() != Some(OsStr::new("json")){
+                self.problems.push(ModelError::new(&file, None, ErrorCause::NotAModelFile));
+                continue;
             }
-            else{
-
-                let file_name = entry.file_name().to_str().unwrap().to_owned();
-                let path = entry.path();
-                let content = fs::read_to_string(&path)?;
-
-                if file_name.as_str() == "mod.json"{
-                    self.files.push((MaterialIdentifier::clone_new_from(&self.name), content));
-                }
-                else{
-                    self.files.push((MaterialIdentifier::clone_new_from(&self.name), content));
-                }
+
+            //inorganic/granite.json and inorganic/granite/mod.json both end up as INORGANIC:GRANITE
+            let identifier = MaterialIdentifier::from_path(path.strip_prefix(folder).unwrap_or(&path));
+            if let Some(other) = identifiers.get(&identifier){
+                let cause = ErrorCause::DuplicateIdentifier{ identifier: identifier.to_string(), other: other.clone() };
+                self.problems.push(ModelError::new(&file, None, cause));
+                continue;
             }
+            identifiers.insert(identifier.clone(), file.clone());
+
+            self.read(path.clone(), file);
+            self.entries.push((identifier, path));
         }
-        Ok(())
+
+        self.read_inherited(tree);
     }
 
-    pub fn load<'a>(&'a self) -> Result<LoadedModels<'a>, Box<dyn Error>>{
+    fn read(&mut self, path: PathBuf, file: PathBuf){
+        let content = fs::read_to_string(&file).map_err(|err| err.to_string());
+        self.sources.insert(path, Source{ file, content });
+    }
 
-        let mut out = BTreeMap::new();
+    ///
+    /// Missing targets are left out, baking reports them where they are inherited.
+    fn read_inherited(&mut self, tree: &impl AssetTree){
+        let mut pending = self.sources.keys().cloned().collect::<Vec<_>>();
+
+        while let Some(path) = pending.pop(){
+            let Ok(content) = &self.sources[&path].content else{
+                continue;
+            };
+            let Ok(Inherits{ inherits: Some(src) }) = serde_json::from_str(content) else{
+                continue;
+            };
+
+            let target = resolve(&path, &src);
+            if self.sources.contains_key(&target){
+                continue;
+            }
+            if let Some(file) = tree.find(&target){
+                self.read(target.clone(), file);
+                pending.push(target);
+            }
+        }
+    }
 
-        for (path,content ) in &self.files{
-            let json = serde_json::de::from_str::<ModelFile>(content)?;
-            out.insert(path, json);
+    pub fn load(&self) -> LoadedModels<'_>{
+        let mut files = BTreeMap::new();
+
+        for (path, source) in &self.sources{
+            let (content, model) = match &source.content{
+                Ok(content) => {
+                    let model = serde_json::from_str::<ModelFile>(content).map_err(|err|{
+                        //The position is kept in the span instead
+                        let message = err.to_string();
+                        let message = message.rfind(" at line ").map_or(message.as_str(), |x| &message[..x]).to_owned();
+                        let span = Span{ line: err.line(), column: err.column() };
+                        ModelError::new(&source.file, Some(span), ErrorCause::Parse(message))
+                    });
+                    (content.as_str(), model)
+                },
+                Err(err) => ("", Err(ModelError::new(&source.file, None, ErrorCause::Io(err.clone())))),
+            };
+
+            files.insert(path.as_path(), LoadedFile{ file: &source.file, source: content, model });
         }
-        Ok(LoadedModels::new(out))
+
+        LoadedModels::new(&self.entries, files, self.problems.clone())
     }
 }
 
+#[cfg(test)]
 mod test{
     use std::path::PathBuf;
 
-    use crate::format::{ModelFile, ShapeKinds, ShapeEntry, VisibilityDefinition, Visibility};
+    use crate::{format::{ModelFile, ShapeKinds, ShapeEntry, VisibilityDefinition, Visibility}, loading::Folder};
 
     use super::Scanner;
 
     #[test]
     fn load_models(){
         let mut scanner = Scanner::new();
-        scanner.scan_dir(&PathBuf::from("./test_files")).unwrap();
-        let models = scanner.load().unwrap();
+        scanner.scan(&Folder(PathBuf::from("./test_files/scanner")), "");
+        let models = scanner.load();
         assert!(models.num_entries() == 1);
         let expected_model = ModelFile{
-            inherits: None,
             shape_kinds: ShapeKinds{
                 wall: Some(ShapeEntry{
-                    model: vec![
+                    model: Some(vec![
 
-                    ],
+                    ]),
                     visibility: VisibilityDefinition{
                         all: Some(Visibility::Solid),
                         ..Default::default()
-                    }
+                    },
+                    ..Default::default()
                 }),
-                floor: None
-            }
+                ..Default::default()
+            },
+            ..Default::default()
         };
 
         assert!(models.contains(&expected_model));
     }
-}
\ No newline at end of file
+}
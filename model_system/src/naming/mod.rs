@@ -1,15 +1,46 @@
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+use std::{fmt::Display, path::Path};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub struct MaterialIdentifier(Box<[Element]>);
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum Element{
     Custom(String)
 }
 
+impl Element{
+    pub fn as_str(&self) -> &str{
+        match self{
+            Element::Custom(x) => x,
+        }
+    }
+}
+
 impl MaterialIdentifier{
-    pub fn clone_new_from(vec: &Vec<Element>) -> Self{
-        let clone = vec.clone();
-        let clone = clone.into_boxed_slice();
-        Self(clone)
+    ///
+    /// Identifier of a model file by its path below the scanned folder,
+    /// "inorganic/granite.json" and "inorganic/granite/mod.json" are both INORGANIC:GRANITE, the root mod.json is the default model.
+    pub fn from_path(path: &Path) -> Self{
+        let mut out = Vec::new();
+        for x in path.iter(){
+            let x = x.to_string_lossy();
+            if x == "mod.json"{
+                continue;
+            }
+            let x = x.strip_suffix(".json").unwrap_or(&x);
+            out.push(Element::Custom(x.to_owned()));
+        }
+        Self(out.into_boxed_slice())
+    }
+
+    pub fn elements(&self) -> &[Element]{
+        &self.0
     }
-}
\ No newline at end of file
+}
+
+impl Display for MaterialIdentifier{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let elements = self.0.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+        write!(f, "{}", elements.join(":"))
+    }
+}
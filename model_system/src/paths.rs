@@ -0,0 +1,93 @@
+use std::path::{Component, Path, PathBuf};
+
+///
+/// Resolves a path written in a model file to a path inside the asset tree, which pack the file comes from is decided later.
+/// "./x.json" and "../x.json" are relative to the including file,
+/// anything else ("materials/mod.json", "/models/stair.obj") is relative to the asset root.
+pub fn resolve(including: &Path, src: &str) -> PathBuf {
+    let path = if src.starts_with("./") || src.starts_with("../") {
+        including.parent().unwrap_or(Path::new("")).join(src)
+    }
+    else {
+        PathBuf::from(src.trim_start_matches('/'))
+    };
+
+    normalize(&path)
+}
+
+///
+/// Path inside the asset tree as the asset server writes it, None if it climbs out of the asset tree.
+pub fn to_asset_path(path: &Path) -> Option<String> {
+    let path = normalize(path);
+    let parts = path.components()
+        .map(|x| match x {
+            Component::Normal(x) => Some(x.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(parts.join("/"))
+}
+
+///
+/// Folds away "." and ".." without touching the file system, the files may not exist.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            //A ".." already climbing out of the tree can't be folded away by the next one
+            Component::ParentDir => {
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) {
+                    out.pop();
+                }
+                else {
+                    out.push("..");
+                }
+            },
+            x => out.push(x),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{resolve, to_asset_path};
+
+    #[test]
+    fn paths_resolve_against_root_or_including_file() {
+        //ARRANGE
+        let including = Path::new("materials/inorganic/mod.json");
+
+        //ACT
+        let rooted = resolve(including, "materials/mod.json");
+        let slashed = resolve(including, "/models/stair.obj");
+        let sibling = resolve(including, "./granite.json");
+        let parent = resolve(including, "../mod.json");
+
+        //ASSERT
+        assert_eq!(rooted, PathBuf::from("materials/mod.json"));
+        assert_eq!(slashed, PathBuf::from("models/stair.obj"));
+        assert_eq!(sibling, PathBuf::from("materials/inorganic/granite.json"));
+        assert_eq!(parent, PathBuf::from("materials/mod.json"));
+    }
+
+    #[test]
+    fn asset_paths_stay_inside_root() {
+        //ARRANGE
+        let including = Path::new("materials/inorganic/mod.json");
+
+        //ACT
+        let inside = to_asset_path(&resolve(including, "../../textures/stone/grey.png"));
+        let outside = to_asset_path(&resolve(including, "../../../secret.png"));
+        let far_outside = to_asset_path(&resolve(including, "../../../../secret.png"));
+
+        //ASSERT
+        assert_eq!(inside, Some("textures/stone/grey.png".to_owned()));
+        assert_eq!(outside, None);
+        assert_eq!(far_outside, None);
+    }
+}
@@ -0,0 +1,118 @@
+use glam::Vec3;
+
+pub use self::{parser::{parse, SdfError}, surface_nets::{surface_nets, SdfMesh}};
+
+mod parser;
+mod surface_nets;
+
+///
+/// A signed distance field in tile coordinates, negative inside the surface.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sdf{
+    Sphere{
+        center: Vec3,
+        radius: f32,
+    },
+    /// A sphere stretched by `radii` along each axis, the distance is only a bound but close enough to mesh
+    Ellipsoid{
+        center: Vec3,
+        radii: Vec3,
+    },
+    Aabb{
+        min: Vec3,
+        max: Vec3,
+    },
+    /// Union
+    Min(Vec<Sdf>),
+    /// Intersection
+    Max(Vec<Sdf>),
+    /// Union blending the shapes into each other within `k`
+    SmoothMin{
+        k: f32,
+        items: Vec<Sdf>,
+    },
+    /// The first shape with the second carved out of it
+    Subtract(Box<Sdf>, Box<Sdf>),
+    /// The shape grown by `radius`, which rounds its edges
+    Round(Box<Sdf>, f32),
+}
+
+impl Sdf{
+    pub fn distance(&self, p: Vec3) -> f32{
+        match self{
+            Sdf::Sphere{ center, radius } => (p - *center).length() - radius,
+            Sdf::Ellipsoid{ center, radii } => {
+                let p = p - *center;
+                let k0 = (p / *radii).length();
+                let k1 = (p / (*radii * *radii)).length();
+                if k1 == 0.0{
+                    -radii.min_element()
+                }
+                else{
+                    k0 * (k0 - 1.0) / k1
+                }
+            },
+            Sdf::Aabb{ min, max } => {
+                let q = (p - (*min + *max) / 2.0).abs() - (*max - *min) / 2.0;
+                q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+            },
+            Sdf::Min(items) => items.iter().map(|x| x.distance(p)).fold(f32::INFINITY, f32::min),
+            Sdf::Max(items) => items.iter().map(|x| x.distance(p)).fold(f32::NEG_INFINITY, f32::max),
+            Sdf::SmoothMin{ k, items } => {
+                let mut distances = items.iter().map(|x| x.distance(p));
+                let first = distances.next().unwrap_or(f32::INFINITY);
+                distances.fold(first, |a, b| smooth_min(a, b, *k))
+            },
+            Sdf::Subtract(a, b) => a.distance(p).max(-b.distance(p)),
+            Sdf::Round(x, radius) => x.distance(p) - radius,
+        }
+    }
+
+    ///
+    /// Direction the distance grows fastest in at `p`, the surface normal when `p` is on the surface.
+    pub fn normal(&self, p: Vec3) -> Vec3{
+        const EPSILON: f32 = 0.0005;
+        let gradient = Vec3::new(
+            self.distance(p + Vec3::X * EPSILON) - self.distance(p - Vec3::X * EPSILON),
+            self.distance(p + Vec3::Y * EPSILON) - self.distance(p - Vec3::Y * EPSILON),
+            self.distance(p + Vec3::Z * EPSILON) - self.distance(p - Vec3::Z * EPSILON),
+        );
+        gradient.normalize_or_zero()
+    }
+}
+
+///
+/// Polynomial smooth minimum, the same as `min` once `a` and `b` are `k` apart.
+fn smooth_min(a: f32, b: f32, k: f32) -> f32{
+    if k <= 0.0{
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - h * h * k / 4.0
+}
+
+#[cfg(test)]
+mod tests{
+    use glam::Vec3;
+
+    use super::Sdf;
+
+    #[test]
+    fn subtract_carves_out_of_box(){
+        //ARRANGE
+        let sdf = Sdf::Subtract(
+            Box::new(Sdf::Aabb{ min: Vec3::splat(-0.5), max: Vec3::splat(0.5) }),
+            Box::new(Sdf::Sphere{ center: Vec3::new(0.0, 0.5, 0.0), radius: 0.25 }),
+        );
+
+        //ACT
+        let carved = sdf.distance(Vec3::new(0.0, 0.4, 0.0));
+        let solid = sdf.distance(Vec3::new(0.0, -0.4, 0.0));
+        let outside = sdf.distance(Vec3::new(0.0, 0.0, 1.0));
+
+        //ASSERT
+        assert!(carved > 0.0);
+        assert!((solid + 0.1).abs() < 0.0001);
+        assert!((outside - 0.5).abs() < 0.0001);
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt::Display;
+
+use glam::Vec3;
+
+use super::Sdf;
+
+const FUNCTIONS: &str = "sphere, ellipsoid, aabb, min, max, smooth_min, subtract, round";
+
+///
+/// A problem in an SDF expression, `offset` is the byte it was found at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdfError{
+    pub offset: usize,
+    pub message: String,
+}
+
+impl Display for SdfError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+///
+/// Parses an SDF expression, nested function calls with numbers and [x, y, z] vectors as arguments:
+/// `sphere(center, radius)`, `ellipsoid(center, radii)`, `aabb(min, max)`, `min(a, b, ..)`, `max(a, b, ..)`,
+/// `smooth_min(k, a, b, ..)`, `subtract(a, b)` and `round(a, radius)`.
+pub fn parse(source: &str) -> Result<Sdf, SdfError>{
+    let mut parser = Parser{ source, pos: 0 };
+    let sdf = parser.node()?;
+
+    parser.skip_whitespace();
+    if parser.pos < source.len(){
+        return Err(parser.error("unexpected text after the expression"));
+    }
+    Ok(sdf)
+}
+
+struct Parser<'a>{
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a>{
+    fn error(&self, message: impl ToString) -> SdfError{
+        SdfError{
+            offset: self.pos,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self){
+        let rest = &self.source[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<u8>{
+        self.skip_whitespace();
+        self.source.as_bytes().get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), SdfError>{
+        if self.peek() != Some(c){
+            return Err(self.error(format!("expected '{}'", c as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    ///
+    /// Consumes the longest run of `pattern` characters.
+    fn take(&mut self, pattern: impl Fn(u8) -> bool) -> &'a str{
+        self.skip_whitespace();
+        let start = self.pos;
+        while matches!(self.source.as_bytes().get(self.pos), Some(x) if pattern(*x)){
+            self.pos += 1;
+        }
+        &self.source[start..self.pos]
+    }
+
+    fn number(&mut self) -> Result<f32, SdfError>{
+        self.skip_whitespace();
+        let start = self.pos;
+        let number = self.take(|x| x.is_ascii_digit() || matches!(x, b'-' | b'+' | b'.' | b'e' | b'E'));
+        number.parse().map_err(|_|{
+            self.pos = start;
+            self.error("expected a number")
+        })
+    }
+
+    fn vector(&mut self) -> Result<Vec3, SdfError>{
+        if self.peek() != Some(b'['){
+            return Err(self.error("expected a vector [x, y, z]"));
+        }
+        self.expect(b'[')?;
+        let x = self.number()?;
+        self.expect(b',')?;
+        let y = self.number()?;
+        self.expect(b',')?;
+        let z = self.number()?;
+        self.expect(b']')?;
+        Ok(Vec3::new(x, y, z))
+    }
+
+    ///
+    /// One or more nodes separated by commas.
+    fn nodes(&mut self) -> Result<Vec<Sdf>, SdfError>{
+        let mut out = vec![self.node()?];
+        while self.peek() == Some(b','){
+            self.pos += 1;
+            out.push(self.node()?);
+        }
+        Ok(out)
+    }
+
+    fn node(&mut self) -> Result<Sdf, SdfError>{
+        self.skip_whitespace();
+        let start = self.pos;
+        let name = self.take(|x| x.is_ascii_alphanumeric() || x == b'_');
+        self.expect(b'(')?;
+
+        let sdf = match name{
+            "sphere" => {
+                let center = self.vector()?;
+                self.expect(b',')?;
+                Sdf::Sphere{ center, radius: self.number()? }
+            },
+            "ellipsoid" => {
+                let center = self.vector()?;
+                self.expect(b',')?;
+                Sdf::Ellipsoid{ center, radii: self.vector()? }
+            },
+            "aabb" => {
+                let min = self.vector()?;
+                self.expect(b',')?;
+                Sdf::Aabb{ min, max: self.vector()? }
+            },
+            "min" => Sdf::Min(self.nodes()?),
+            "max" => Sdf::Max(self.nodes()?),
+            "smooth_min" => {
+                let k = self.number()?;
+                self.expect(b',')?;
+                Sdf::SmoothMin{ k, items: self.nodes()? }
+            },
+            "subtract" => {
+                let a = self.node()?;
+                self.expect(b',')?;
+                Sdf::Subtract(Box::new(a), Box::new(self.node()?))
+            },
+            "round" => {
+                let a = self.node()?;
+                self.expect(b',')?;
+                Sdf::Round(Box::new(a), self.number()?)
+            },
+            _ => {
+                self.pos = start;
+                return Err(self.error(format!("unknown SDF function '{}', expected one of {}", name, FUNCTIONS)));
+            },
+        };
+
+        self.expect(b')')?;
+        Ok(sdf)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use glam::Vec3;
+
+    use crate::sdf::Sdf;
+
+    use super::{parse, SdfError};
+
+    #[test]
+    fn nested_functions_are_parsed(){
+        //ARRANGE
+        let source = "round(min(sphere([0, 0.1, 0], 0.3), aabb([-0.5, -0.5, -0.5], [0.5, -0.3, 0.5])), 0.05)";
+
+        //ACT
+        let sdf = parse(source);
+
+        //ASSERT
+        assert_eq!(sdf, Ok(Sdf::Round(Box::new(Sdf::Min(vec![
+            Sdf::Sphere{ center: Vec3::new(0.0, 0.1, 0.0), radius: 0.3 },
+            Sdf::Aabb{ min: Vec3::splat(-0.5), max: Vec3::new(0.5, -0.3, 0.5) },
+        ])), 0.05)));
+    }
+
+    #[test]
+    fn errors_point_at_the_problem(){
+        //ARRANGE
+        let unknown = "min(sphere([0, 0, 0], 0.3), cube([0, 0, 0], 1))";
+        let trailing = "sphere([0, 0, 0], 0.3) x";
+
+        //ACT
+        let unknown = parse(unknown).map_err(|x| x.offset);
+        let trailing = parse(trailing);
+
+        //ASSERT
+        assert_eq!(unknown, Err(28));
+        assert_eq!(trailing, Err(SdfError{ offset: 23, message: "unexpected text after the expression".to_owned() }));
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec3};
+
+use crate::format::{Bound, Side};
+
+use super::Sdf;
+
+/// Cells per tile along each axis, one for every pixel of a 16x16 tile texture
+const CELLS_PER_TILE: f32 = 16.0;
+/// Bounds spanning more than 4 tiles get coarser cells, so a mistyped bound can't allocate the whole memory
+const MAX_CELLS: f32 = 64.0;
+/// Vertices the surface of a tile may use, so a chunk full of SDF tiles stays cheap to mesh and draw
+const MAX_VERTS: usize = 1024;
+
+///
+/// Triangles of an SDF surface in tile coordinates, uvs are projected from the tile face the surface faces most.
+#[derive(Debug, Default)]
+pub struct SdfMesh{
+    pub verts: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub normals: Vec<Vec3>,
+    pub indices: Vec<u16>,
+}
+
+///
+/// Samples `sdf` on a grid covering `bound` and places a vertex in every cell the surface passes through,
+/// the surface is cut off at the bound and closed there.
+/// Surfaces needing more than [`MAX_VERTS`] vertices are sampled coarser until they fit.
+pub fn surface_nets(sdf: &Sdf, bound: &Bound) -> SdfMesh{
+    //The surface is clipped to the bound by intersecting it with the bound box
+    let field = Sdf::Max(vec![sdf.clone(), Sdf::Aabb{ min: bound.min.into(), max: bound.max.into() }]);
+
+    let mut cells = (bound.size() * CELLS_PER_TILE).ceil().clamp(Vec3::ONE, Vec3::splat(MAX_CELLS));
+    loop{
+        //A single cell per axis leaves 27 cells for the surface to pass through, which always fits
+        match mesh_cells(&field, bound, cells){
+            Some(mesh) => return mesh,
+            None => cells = (cells / 2.0).ceil(),
+        }
+    }
+}
+
+///
+/// Runs surface nets with `cells` cells along each axis, [`None`] if the surface needs more than [`MAX_VERTS`] vertices.
+fn mesh_cells(field: &Sdf, bound: &Bound, cells: Vec3) -> Option<SdfMesh>{
+    let step = bound.size() / cells;
+    //One layer of samples outside the bound on every side, so every sign change has a full ring of cells around it
+    let size = [cells.x as usize + 3, cells.y as usize + 3, cells.z as usize + 3];
+    let origin = Vec3::from(bound.min) - step;
+
+    let point = |[x, y, z]: [usize; 3]| origin + step * Vec3::new(x as f32, y as f32, z as f32);
+    let index = |[x, y, z]: [usize; 3]| x + size[0] * (y + size[1] * z);
+
+    let mut samples = vec![0.0; size[0] * size[1] * size[2]];
+    for z in 0..size[2]{
+        for y in 0..size[1]{
+            for x in 0..size[0]{
+                samples[index([x, y, z])] = field.distance(point([x, y, z]));
+            }
+        }
+    }
+    let inside = |p: [usize; 3]| samples[index(p)] < 0.0;
+
+    //Vertex of every cell with the surface passing through it, at the average of the crossings on its edges
+    let mut cell_verts = vec![None; samples.len()];
+    for z in 0..size[2] - 1{
+        for y in 0..size[1] - 1{
+            for x in 0..size[0] - 1{
+                let mut sum = Vec3::ZERO;
+                let mut count = 0;
+                for axis in 0..3{
+                    for corner in 0..4{
+                        let mut a = [x, y, z];
+                        a[(axis + 1) % 3] += corner & 1;
+                        a[(axis + 2) % 3] += corner >> 1;
+                        let mut b = a;
+                        b[axis] += 1;
+
+                        let (da, db) = (samples[index(a)], samples[index(b)]);
+                        if (da < 0.0) != (db < 0.0){
+                            sum += point(a).lerp(point(b), da / (da - db));
+                            count += 1;
+                        }
+                    }
+                }
+
+                if count > 0{
+                    cell_verts[index([x, y, z])] = Some(sum / count as f32);
+                }
+            }
+        }
+    }
+
+    //Every grid edge crossing the surface gets a quad joining the four cells around it
+    let mut mesh = SdfMesh::default();
+    let mut shared = HashMap::new();
+    for z in 1..size[2] - 1{
+        for y in 1..size[1] - 1{
+            for x in 1..size[0] - 1{
+                let a = [x, y, z];
+                for axis in 0..3{
+                    let mut b = a;
+                    b[axis] += 1;
+                    if b[axis] >= size[axis] || inside(a) == inside(b){
+                        continue;
+                    }
+
+                    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                    let mut ring = [a; 4];
+                    ring[0][u] -= 1;
+                    ring[0][v] -= 1;
+                    ring[1][v] -= 1;
+                    ring[3][u] -= 1;
+
+                    let Some(mut quad) = ring.iter().map(|x| Some((index(*x), cell_verts[index(*x)]?))).collect::<Option<Vec<_>>>() else{
+                        continue;
+                    };
+                    //The ring goes counter-clockwise seen from the end of the edge, the surface has to face away from the inside
+                    if !inside(a){
+                        quad.reverse();
+                    }
+                    push_quad(&mut mesh, &mut shared, field, &quad);
+                    if mesh.verts.len() > MAX_VERTS{
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+
+    Some(mesh)
+}
+
+///
+/// Adds a quad of (cell, vertex) pairs, reusing the vertices of neighbouring quads projected onto the same side.
+fn push_quad(mesh: &mut SdfMesh, shared: &mut HashMap<(usize, Side), u16>, field: &Sdf, quad: &[(usize, Vec3)]){
+    //One projection for the whole quad, so its texture isn't torn apart where the surface turns
+    let facing = quad.iter().map(|(_, x)| field.normal(*x)).sum::<Vec3>();
+    let side = Side::ALL.into_iter()
+        .max_by(|a, b| a.axes()[1].dot(facing).total_cmp(&b.axes()[1].dot(facing)))
+        .unwrap_or(Side::Up);
+    let [u_axis, _, v_axis] = side.axes();
+
+    let corners = quad.iter().map(|(cell, vert)| *shared.entry((*cell, side)).or_insert_with(|| {
+        //Stays below u16::MAX, the caller gives up once the mesh has more than MAX_VERTS
+        let index = mesh.verts.len() as u16;
+        mesh.verts.push(*vert);
+        mesh.normals.push(field.normal(*vert));
+        let uv = Vec2::new(vert.dot(u_axis), vert.dot(v_axis)) + 0.5;
+        mesh.uvs.push(uv.clamp(Vec2::ZERO, Vec2::ONE));
+        index
+    })).collect::<Vec<_>>();
+
+    mesh.indices.extend([0, 1, 2, 0, 2, 3].map(|x| corners[x]));
+}
+
+#[cfg(test)]
+mod tests{
+    use glam::{Vec2, Vec3};
+
+    use crate::{format::Bound, sdf::Sdf};
+
+    use super::{surface_nets, MAX_VERTS};
+
+    #[test]
+    fn sphere_faces_outwards(){
+        //ARRANGE
+        let sdf = Sdf::Sphere{ center: Vec3::ZERO, radius: 0.3 };
+
+        //ACT
+        let mesh = surface_nets(&sdf, &Bound::default());
+
+        //ASSERT
+        assert!(!mesh.indices.is_empty());
+        for (vert, normal) in mesh.verts.iter().zip(&mesh.normals){
+            assert!((vert.length() - 0.3).abs() < 0.01);
+            assert!(normal.dot(vert.normalize()) > 0.99);
+        }
+        for triangle in mesh.indices.chunks(3){
+            let [a, b, c] = [0, 1, 2].map(|x| mesh.verts[triangle[x] as usize]);
+            let face = (b - a).cross(c - a);
+            assert!(face.dot(a) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn surface_is_cut_off_at_the_bound(){
+        //ARRANGE
+        let sdf = Sdf::Sphere{ center: Vec3::ZERO, radius: 0.4 };
+        let bound = Bound{ min: [-0.5, -0.5, -0.5], max: [0.5, 0.0, 0.5] };
+
+        //ACT
+        let mesh = surface_nets(&sdf, &bound);
+
+        //ASSERT
+        assert!(mesh.verts.iter().all(|x| x.y <= 0.0001));
+        assert!(mesh.normals.iter().any(|x| x.abs_diff_eq(Vec3::Y, 0.0001)));
+        assert!(mesh.uvs.iter().all(|x| x.cmpge(Vec2::ZERO).all() && x.cmple(Vec2::ONE).all()));
+    }
+
+    #[test]
+    fn huge_bounds_are_sampled_coarser(){
+        //ARRANGE
+        let sdf = Sdf::Sphere{ center: Vec3::ZERO, radius: 40.0 };
+        let bound = Bound{ min: [-100.0, -100.0, -100.0], max: [100.0, 100.0, 100.0] };
+
+        //ACT
+        let mesh = surface_nets(&sdf, &bound);
+
+        //ASSERT
+        assert!(!mesh.indices.is_empty());
+        assert!(mesh.verts.iter().all(|x| (x.length() - 40.0).abs() < 4.0));
+    }
+
+    #[test]
+    fn neighbouring_cells_share_vertices(){
+        //ARRANGE
+        let sdf = Sdf::Sphere{ center: Vec3::ZERO, radius: 0.3 };
+
+        //ACT
+        let mesh = surface_nets(&sdf, &Bound::default());
+
+        //ASSERT
+        //Every vertex of a closed net joins about four quads, unshared quads would use each of their vertices once
+        assert!(mesh.indices.len() > 3 * mesh.verts.len());
+    }
+
+    #[test]
+    fn detailed_surfaces_are_sampled_coarser_to_fit_the_vertex_budget(){
+        //ARRANGE
+        let sdf = Sdf::Aabb{ min: Vec3::splat(-0.45), max: Vec3::splat(0.45) };
+
+        //ACT
+        let mesh = surface_nets(&sdf, &Bound::default());
+
+        //ASSERT
+        assert!(!mesh.indices.is_empty());
+        assert!(mesh.verts.len() <= MAX_VERTS);
+        assert!(mesh.verts.iter().all(|x| x.abs().max_element() < 0.5));
+    }
+}
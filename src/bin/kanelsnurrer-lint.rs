@@ -0,0 +1,39 @@
+use std::process::ExitCode;
+
+use volum2::{loaders::{asset_packs::AssetPacks, lint::lint_assets}, util::args::has_flag};
+
+///
+/// Checks every model file in the asset folder and the packs layered over it, without starting the viewer.
+/// `--list-packs` only lists the packs that are in use.
+fn main() -> ExitCode {
+    let (packs, pack_problems) = AssetPacks::load();
+    for problem in &pack_problems {
+        println!("{}", problem);
+    }
+
+    if has_flag("--list-packs") {
+        println!("asset packs, later ones override earlier ones:");
+        for pack in packs.packs() {
+            println!("{} ({})", pack.name, pack.root.display());
+        }
+        return if pack_problems.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE };
+    }
+
+    println!("checking {}", packs);
+
+    let problems = lint_assets(&packs);
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    let count = pack_problems.len() + problems.len();
+    if count == 0 {
+        println!("no problems found");
+        ExitCode::SUCCESS
+    }
+    else {
+        println!("{} problems found", count);
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,240 @@
+use std::{collections::{BTreeMap, BTreeSet}, error::Error, path::Path};
+
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{
+    BlockList, BlockRequest, BuildingList, CreatureRawList, MapBlock, MapInfo, MaterialList, TiletypeList, UnitList,
+};
+
+use super::{FortressSource, session::{self, Response, SessionWriter}};
+
+///
+/// A fortress held entirely in memory, for running the viewer and its tests without Dwarf Fortress.
+/// Blocks are keyed by block coordinates, (map_x / 16, map_y / 16, map_z), the same units a [`BlockRequest`] uses.
+/// Like DFHack, a block is only sent again once it changed or the map hashes were reset.
+#[derive(Default)]
+pub struct MemoryFortress {
+    map_info: MapInfo,
+    material_list: MaterialList,
+    tiletype_list: TiletypeList,
+    building_list: BuildingList,
+    /// Only the latest unit list is kept, a replay shows every unit where it was last seen.
+    unit_list: UnitList,
+    creature_raws: CreatureRawList,
+    blocks: BTreeMap<(i32, i32, i32), MapBlock>,
+    /// Blocks sent since they last changed.
+    served: BTreeSet<(i32, i32, i32)>,
+}
+
+impl MemoryFortress {
+    pub fn new(map_info: MapInfo, material_list: MaterialList, tiletype_list: TiletypeList) -> Self {
+        Self {
+            map_info,
+            material_list,
+            tiletype_list,
+            building_list: BuildingList::default(),
+            unit_list: UnitList::default(),
+            creature_raws: CreatureRawList::default(),
+            blocks: BTreeMap::new(),
+            served: BTreeSet::new(),
+        }
+    }
+
+    pub fn set_building_list(&mut self, building_list: BuildingList) {
+        self.building_list = building_list;
+    }
+
+    pub fn insert_block(&mut self, block: MapBlock) {
+        let key = block_key(&block);
+        self.served.remove(&key);
+        self.blocks.insert(key, block);
+    }
+
+    pub fn apply(&mut self, response: Response) {
+        match response {
+            Response::MapInfo(info) => self.map_info = info,
+            Response::MaterialList(list) => self.material_list = list,
+            Response::TiletypeList(list) => self.tiletype_list = list,
+            Response::BuildingList(list) => self.building_list = list,
+            Response::UnitList(list) => self.unit_list = list,
+            Response::CreatureRawList(list) => self.creature_raws = list,
+            Response::BlockList(list) => {
+                for block in list.map_blocks {
+                    self.insert_block(block);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Reads a session file, later records replacing earlier ones.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut fortress = Self::default();
+        for response in session::read_session(path)? {
+            fortress.apply(response);
+        }
+        Ok(fortress)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut writer = SessionWriter::create(path)?;
+        writer.write(Response::MapInfo(self.map_info.clone()))?;
+        writer.write(Response::MaterialList(self.material_list.clone()))?;
+        writer.write(Response::TiletypeList(self.tiletype_list.clone()))?;
+        writer.write(Response::BuildingList(self.building_list.clone()))?;
+        writer.write(Response::CreatureRawList(self.creature_raws.clone()))?;
+        writer.write(Response::UnitList(self.unit_list.clone()))?;
+        writer.write(Response::BlockList(BlockList {
+            map_blocks: self.blocks.values().cloned().collect(),
+            ..Default::default()
+        }))?;
+        Ok(())
+    }
+}
+
+fn block_key(block: &MapBlock) -> (i32, i32, i32) {
+    (block.map_x.div_euclid(16), block.map_y.div_euclid(16), block.map_z)
+}
+
+impl FortressSource for MemoryFortress {
+    fn reset_map_hashes(&mut self) {
+        self.served.clear();
+    }
+
+    fn get_map_info(&mut self) -> MapInfo {
+        self.map_info.clone()
+    }
+
+    fn get_material_list(&mut self) -> MaterialList {
+        self.material_list.clone()
+    }
+
+    fn get_tile_type_list(&mut self) -> TiletypeList {
+        self.tiletype_list.clone()
+    }
+
+    fn get_building_def_list(&mut self) -> BuildingList {
+        self.building_list.clone()
+    }
+
+    fn get_unit_list(&mut self) -> UnitList {
+        self.unit_list.clone()
+    }
+
+    fn get_creature_raws(&mut self) -> CreatureRawList {
+        self.creature_raws.clone()
+    }
+
+    fn get_block_list(&mut self, request: BlockRequest) -> BlockList {
+        let limit = request.blocks_needed.map(|x| x.max(0) as usize).unwrap_or(usize::MAX);
+
+        let keys = self.blocks
+            .keys()
+            .filter(|key| !self.served.contains(key))
+            .filter(|(x, y, z)| {
+                request.min_x.map_or(true, |min| *x >= min) && request.max_x.map_or(true, |max| *x < max) &&
+                request.min_y.map_or(true, |min| *y >= min) && request.max_y.map_or(true, |max| *y < max) &&
+                request.min_z.map_or(true, |min| *z >= min) && request.max_z.map_or(true, |max| *z < max)
+            })
+            .take(limit)
+            .copied()
+            .collect::<Vec<_>>();
+
+        self.served.extend(keys.iter().copied());
+        let map_blocks = keys.iter().map(|key| self.blocks[key].clone()).collect();
+
+        BlockList {
+            map_blocks,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{BlockRequest, MapBlock, MapInfo};
+
+    use crate::{fortress::FortressSource, util::test_files::temp_path};
+
+    use super::MemoryFortress;
+
+    fn block(x: i32, y: i32, z: i32) -> MapBlock {
+        MapBlock {
+            map_x: x * 16,
+            map_y: y * 16,
+            map_z: z,
+            ..Default::default()
+        }
+    }
+
+    fn request(x: i32, y: i32, z: (i32, i32)) -> BlockRequest {
+        BlockRequest {
+            blocks_needed: Some(4096),
+            min_x: Some(x),
+            max_x: Some(x + 1),
+            min_y: Some(y),
+            max_y: Some(y + 1),
+            min_z: Some(z.0),
+            max_z: Some(z.1),
+        }
+    }
+
+    #[test]
+    fn get_block_list_returns_blocks_inside_request(){
+        //ARRANGE
+        let mut fortress = MemoryFortress::default();
+        fortress.insert_block(block(0, 0, 0));
+        fortress.insert_block(block(0, 0, 15));
+        fortress.insert_block(block(0, 0, 16));
+        fortress.insert_block(block(1, 0, 0));
+
+        //ACT
+        let list = fortress.get_block_list(request(0, 0, (0, 16)));
+
+        //ASSERT
+        let positions = list.map_blocks.iter().map(|x| (x.map_x, x.map_y, x.map_z)).collect::<Vec<_>>();
+        assert_eq!(positions, vec![(0, 0, 0), (0, 0, 15)]);
+    }
+
+    #[test]
+    fn blocks_are_sent_again_only_after_a_change_or_reset(){
+        //ARRANGE
+        let mut fortress = MemoryFortress::default();
+        fortress.insert_block(block(0, 0, 0));
+        fortress.insert_block(block(0, 0, 1));
+        fortress.get_block_list(request(0, 0, (0, 16)));
+
+        //ACT
+        let unchanged = fortress.get_block_list(request(0, 0, (0, 16)));
+        fortress.insert_block(block(0, 0, 1));
+        let changed = fortress.get_block_list(request(0, 0, (0, 16)));
+        fortress.reset_map_hashes();
+        let reset = fortress.get_block_list(request(0, 0, (0, 16)));
+
+        //ASSERT
+        assert!(unchanged.map_blocks.is_empty());
+        assert_eq!(changed.map_blocks.iter().map(|x| x.map_z).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(reset.map_blocks.len(), 2);
+    }
+
+    #[test]
+    fn save_and_load_round_trips(){
+        //ARRANGE
+        let path = temp_path("save_and_load_round_trips");
+        let mut fortress = MemoryFortress::default();
+        fortress.map_info = MapInfo{
+            block_size_x: Some(2),
+            ..Default::default()
+        };
+        fortress.insert_block(block(1, 2, 3));
+
+        //ACT
+        fortress.save(&path).unwrap();
+        let mut copy = MemoryFortress::load(&path).unwrap();
+
+        //ASSERT
+        fs::remove_file(&path).unwrap();
+        assert_eq!(copy.get_map_info().block_size_x(), 2);
+        assert_eq!(copy.get_block_list(request(1, 2, (3, 4))).map_blocks.len(), 1);
+    }
+}
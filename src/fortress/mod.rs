@@ -0,0 +1,106 @@
+use std::{sync::{Arc, Mutex, MutexGuard}, path::PathBuf};
+
+use bevy::prelude::{info, Resource};
+use df_rust::clients::remote_fortress_reader::{
+    RemoteFortressReader,
+    remote_fortress_reader::{BlockList, BlockRequest, BuildingList, CreatureRawList, MapInfo, MaterialList, TiletypeList, UnitList},
+};
+
+use crate::util::args::arg_value;
+
+use self::{memory::MemoryFortress, session::RecordingFortress};
+
+pub mod memory;
+pub mod session;
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:5000";
+
+///
+/// Everything the viewer needs to know about a fortress.
+/// Implemented by the live DFHack connection and by [`MemoryFortress`] for offline use.
+pub trait FortressSource: Send {
+    fn reset_map_hashes(&mut self);
+    fn get_map_info(&mut self) -> MapInfo;
+    fn get_material_list(&mut self) -> MaterialList;
+    fn get_tile_type_list(&mut self) -> TiletypeList;
+    fn get_block_list(&mut self, request: BlockRequest) -> BlockList;
+    fn get_building_def_list(&mut self) -> BuildingList;
+    fn get_unit_list(&mut self) -> UnitList;
+    fn get_creature_raws(&mut self) -> CreatureRawList;
+}
+
+impl FortressSource for RemoteFortressReader {
+    fn reset_map_hashes(&mut self) {
+        RemoteFortressReader::reset_map_hashes(self)
+    }
+
+    fn get_map_info(&mut self) -> MapInfo {
+        RemoteFortressReader::get_map_info(self)
+    }
+
+    fn get_material_list(&mut self) -> MaterialList {
+        RemoteFortressReader::get_material_list(self)
+    }
+
+    fn get_tile_type_list(&mut self) -> TiletypeList {
+        RemoteFortressReader::get_tile_type_list(self)
+    }
+
+    fn get_block_list(&mut self, request: BlockRequest) -> BlockList {
+        RemoteFortressReader::get_block_list(self, request)
+    }
+
+    fn get_building_def_list(&mut self) -> BuildingList {
+        RemoteFortressReader::get_building_def_list(self)
+    }
+
+    fn get_unit_list(&mut self) -> UnitList {
+        RemoteFortressReader::get_unit_list(self)
+    }
+
+    fn get_creature_raws(&mut self) -> CreatureRawList {
+        RemoteFortressReader::get_creature_raws(self)
+    }
+}
+
+///
+/// Shared handle to the active [`FortressSource`].
+/// Cloned into loader tasks so that every request goes through the same connection.
+#[derive(Resource, Clone)]
+pub struct FortressResource(Arc<Mutex<dyn FortressSource>>);
+
+impl FortressResource {
+    pub fn new<S: FortressSource + 'static>(source: S) -> Self {
+        Self(Arc::new(Mutex::new(source)))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, dyn FortressSource + 'static> {
+        self.0.lock().unwrap()
+    }
+
+    ///
+    /// Picks a source from the command line.
+    /// `--replay <file>` serves a recorded session from disk, `--record <file>` writes everything fetched from DFHack to a session file,
+    /// `--address <host:port>` overrides the DFHack address.
+    pub fn from_args() -> Self {
+        let address = arg_value("--address").unwrap_or_else(|| DEFAULT_ADDRESS.to_owned());
+        let replay = arg_value("--replay").map(PathBuf::from);
+        let record = arg_value("--record").map(PathBuf::from);
+
+        match (replay, record) {
+            (Some(path), _) => {
+                info!("replaying session {}", path.display());
+                let fortress = MemoryFortress::load(&path)
+                    .unwrap_or_else(|e| panic!("could not read session {}: {}", path.display(), e));
+                Self::new(fortress)
+            }
+            (None, Some(path)) => {
+                info!("recording session to {}", path.display());
+                let fortress = RecordingFortress::new(RemoteFortressReader::new(Some(&address)), &path)
+                    .unwrap_or_else(|e| panic!("could not create session {}: {}", path.display(), e));
+                Self::new(fortress)
+            }
+            (None, None) => Self::new(RemoteFortressReader::new(Some(&address))),
+        }
+    }
+}
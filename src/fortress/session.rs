@@ -0,0 +1,199 @@
+use std::{fs::{self, File}, io::{self, BufWriter, Write}, path::Path, error::Error};
+
+use bevy::prelude::warn;
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{
+    BlockList, BlockRequest, BuildingList, CreatureRawList, MapInfo, MaterialList, TiletypeList, UnitList,
+};
+use prost::{Message, Oneof};
+
+use super::FortressSource;
+
+///
+/// One response as stored in a session file.
+/// A session file is a sequence of length-delimited records, appended in the order they were fetched.
+#[derive(Clone, PartialEq, Message)]
+pub struct SessionRecord {
+    #[prost(oneof = "Response", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub response: Option<Response>,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+pub enum Response {
+    #[prost(message, tag = "1")]
+    MapInfo(MapInfo),
+    #[prost(message, tag = "2")]
+    MaterialList(MaterialList),
+    #[prost(message, tag = "3")]
+    TiletypeList(TiletypeList),
+    #[prost(message, tag = "4")]
+    BlockList(BlockList),
+    #[prost(message, tag = "5")]
+    BuildingList(BuildingList),
+    #[prost(message, tag = "6")]
+    UnitList(UnitList),
+    #[prost(message, tag = "7")]
+    CreatureRawList(CreatureRawList),
+}
+
+pub struct SessionWriter {
+    file: BufWriter<File>,
+}
+
+impl SessionWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    ///
+    /// Appends the response and flushes, so that a crash keeps everything fetched so far.
+    pub fn write(&mut self, response: Response) -> io::Result<()> {
+        let record = SessionRecord {
+            response: Some(response),
+        };
+        self.file.write_all(&record.encode_length_delimited_to_vec())?;
+        self.file.flush()
+    }
+}
+
+pub fn read_session(path: &Path) -> Result<Vec<Response>, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    let mut buf = bytes.as_slice();
+
+    let mut responses = Vec::new();
+    while !buf.is_empty() {
+        let record = SessionRecord::decode_length_delimited(&mut buf)?;
+        if let Some(response) = record.response {
+            responses.push(response);
+        }
+    }
+    Ok(responses)
+}
+
+///
+/// Forwards every request to `source` and writes each response to a session file.
+/// The file can be served back with [`super::memory::MemoryFortress::load`].
+pub struct RecordingFortress<S> {
+    source: S,
+    writer: SessionWriter,
+    /// Set after the first failed write, so a full disk isn't reported for every response.
+    failed: bool,
+}
+
+impl<S: FortressSource> RecordingFortress<S> {
+    pub fn new(source: S, path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            source,
+            writer: SessionWriter::create(path)?,
+            failed: false,
+        })
+    }
+
+    fn record(&mut self, response: Response) {
+        if let Err(e) = self.writer.write(response) {
+            if !self.failed {
+                warn!("failed to record response, the session will be incomplete: {}", e);
+                self.failed = true;
+            }
+        }
+    }
+}
+
+impl<S: FortressSource> FortressSource for RecordingFortress<S> {
+    fn reset_map_hashes(&mut self) {
+        self.source.reset_map_hashes()
+    }
+
+    fn get_map_info(&mut self) -> MapInfo {
+        let info = self.source.get_map_info();
+        self.record(Response::MapInfo(info.clone()));
+        info
+    }
+
+    fn get_material_list(&mut self) -> MaterialList {
+        let list = self.source.get_material_list();
+        self.record(Response::MaterialList(list.clone()));
+        list
+    }
+
+    fn get_tile_type_list(&mut self) -> TiletypeList {
+        let list = self.source.get_tile_type_list();
+        self.record(Response::TiletypeList(list.clone()));
+        list
+    }
+
+    fn get_building_def_list(&mut self) -> BuildingList {
+        let list = self.source.get_building_def_list();
+        self.record(Response::BuildingList(list.clone()));
+        list
+    }
+
+    fn get_unit_list(&mut self) -> UnitList {
+        let list = self.source.get_unit_list();
+        self.record(Response::UnitList(list.clone()));
+        list
+    }
+
+    fn get_creature_raws(&mut self) -> CreatureRawList {
+        let list = self.source.get_creature_raws();
+        self.record(Response::CreatureRawList(list.clone()));
+        list
+    }
+
+    fn get_block_list(&mut self, request: BlockRequest) -> BlockList {
+        let list = self.source.get_block_list(request);
+        if !list.map_blocks.is_empty() {
+            self.record(Response::BlockList(list.clone()));
+        }
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{BlockRequest, MapBlock, MapInfo};
+
+    use crate::{fortress::{memory::MemoryFortress, FortressSource}, util::test_files::temp_path};
+
+    use super::RecordingFortress;
+
+    #[test]
+    fn recorded_session_replays_the_same_data(){
+        //ARRANGE
+        let path = temp_path("recorded_session_replays_the_same_data");
+        let mut source = MemoryFortress::new(
+            MapInfo{
+                block_size_x: Some(3),
+                ..Default::default()
+            },
+            Default::default(),
+            Default::default()
+        );
+        source.insert_block(MapBlock{
+            map_x: 16,
+            map_y: 32,
+            map_z: 7,
+            tiles: vec![1; 256],
+            ..Default::default()
+        });
+
+        //ACT
+        {
+            let mut recorder = RecordingFortress::new(source, &path).unwrap();
+            recorder.get_map_info();
+            recorder.get_block_list(BlockRequest::default());
+        }
+        let mut replay = MemoryFortress::load(&path).unwrap();
+
+        //ASSERT
+        fs::remove_file(&path).unwrap();
+        assert_eq!(replay.get_map_info().block_size_x(), 3);
+        let blocks = replay.get_block_list(BlockRequest::default()).map_blocks;
+        assert_eq!(blocks.len(), 1);
+        assert_eq!((blocks[0].map_x, blocks[0].map_y, blocks[0].map_z), (16, 32, 7));
+        assert_eq!(blocks[0].tiles, vec![1; 256]);
+    }
+}
@@ -0,0 +1,14 @@
+#![feature(iterator_try_collect)]
+#![feature(maybe_uninit_uninit_array)]
+#![feature(maybe_uninit_array_assume_init)]
+pub mod loaders;
+pub mod fortress;
+pub mod util;
+pub mod voxel;
+pub mod world;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AppState {
+    Setup,
+    Running,
+}
@@ -0,0 +1,248 @@
+use std::{collections::BTreeMap, fmt::Display, fs, io::ErrorKind, path::{Path, PathBuf}};
+
+use bevy::prelude::{warn, Resource};
+use model_system::loading::AssetTree;
+use walkdir::WalkDir;
+
+use super::asset_path::{asset_root, base_path, ASSET_FOLDER};
+
+/// Lists the packs layered over the base assets, in the folder holding the asset folder.
+pub const PACK_CONFIG: &str = "asset_packs.ron";
+
+#[derive(Debug, Clone)]
+pub struct AssetPack {
+    /// As written in the pack config
+    pub name: String,
+    pub root: PathBuf,
+}
+
+///
+/// The base assets followed by the packs from the pack config.
+/// Files are looked up by their path inside a pack, and the last pack having a file wins,
+/// so a pack only needs the material files, textures and meshes it changes.
+#[derive(Resource, Debug, Clone)]
+pub struct AssetPacks {
+    packs: Vec<AssetPack>,
+}
+
+impl AssetPacks {
+    pub fn new(packs: Vec<AssetPack>) -> Self {
+        Self { packs }
+    }
+
+    ///
+    /// Reads the pack config, without one only the base assets are used.
+    /// Also returns what was wrong with the config, a broken config or missing pack doesn't stop the other packs from loading.
+    pub fn load() -> (Self, Vec<String>) {
+        let base = base_path();
+        let mut packs = vec![AssetPack {
+            name: ASSET_FOLDER.to_owned(),
+            root: asset_root(),
+        }];
+
+        let mut problems = Vec::new();
+
+        let config = base.join(PACK_CONFIG);
+        let source = match fs::read_to_string(&config) {
+            Ok(x) => x,
+            Err(err) => {
+                if err.kind() != ErrorKind::NotFound {
+                    problems.push(format!("can't read {}: {}", config.display(), err));
+                }
+                return (Self::new(packs), problems);
+            }
+        };
+
+        match ron::from_str::<Vec<String>>(&source) {
+            Ok(names) => {
+                for name in names {
+                    let root = base.join(&name);
+                    if !root.is_dir() {
+                        problems.push(format!("asset pack {} does not exist, skipping it", root.display()));
+                        continue;
+                    }
+                    packs.push(AssetPack { name, root });
+                }
+            },
+            Err(err) => problems.push(format!("{}:{}: {}, only the base assets are used", config.display(), err.position.line, err.code)),
+        }
+
+        (Self::new(packs), problems)
+    }
+
+    pub fn packs(&self) -> &[AssetPack] {
+        &self.packs
+    }
+
+    ///
+    /// File on disk for a path inside the asset tree, from the last pack having it.
+    pub fn find(&self, path: &Path) -> Option<PathBuf> {
+        self.packs
+            .iter()
+            .rev()
+            .map(|x| x.root.join(path))
+            .find(|x| x.is_file())
+    }
+
+    pub fn has_folder(&self, folder: &str) -> bool {
+        self.packs.iter().any(|x| x.root.join(folder).is_dir())
+    }
+
+    ///
+    /// Every file below `folder` in any pack as (path inside the asset tree, file on disk), sorted by path.
+    /// Folders that can't be read are logged and skipped.
+    pub fn files(&self, folder: &str) -> Vec<(PathBuf, PathBuf)> {
+        let (files, problems) = self.walk(folder);
+        for problem in problems {
+            warn!("{}", problem);
+        }
+        files
+    }
+
+    ///
+    /// [`AssetPacks::files`], returning the entries that couldn't be read instead of logging them.
+    pub fn walk(&self, folder: &str) -> (Vec<(PathBuf, PathBuf)>, Vec<String>) {
+        let mut files = BTreeMap::new();
+        let mut problems = Vec::new();
+
+        for pack in &self.packs {
+            let root = pack.root.join(folder);
+            if !root.is_dir() {
+                continue;
+            }
+
+            for entry in WalkDir::new(root) {
+                let entry = match entry {
+                    Ok(x) => x,
+                    Err(err) => {
+                        problems.push(err.to_string());
+                        continue;
+                    }
+                };
+
+                if entry.file_type().is_file() {
+                    let path = entry.path().strip_prefix(&pack.root).unwrap().to_owned();
+                    files.insert(path, entry.into_path());
+                }
+            }
+        }
+
+        (files.into_iter().collect(), problems)
+    }
+
+    ///
+    /// Path the asset server loads a file on disk from.
+    /// Files outside of the asset folder are loaded by their full path.
+    pub fn server_path(&self, file: &Path) -> String {
+        match file.strip_prefix(&self.packs[0].root) {
+            Ok(path) => path.iter().map(|x| x.to_string_lossy()).collect::<Vec<_>>().join("/"),
+            Err(_) => file.to_string_lossy().into_owned(),
+        }
+    }
+
+    ///
+    /// Path the asset server loads an asset from, taking it from the last pack having it.
+    /// A label after '#' picks an asset inside the file, like a texture embedded in a glTF file, and is kept.
+    pub fn load_path(&self, path: &str) -> String {
+        let (file, label) = match path.split_once('#') {
+            Some((file, label)) => (file, Some(label)),
+            None => (path, None),
+        };
+
+        let file = match self.find(Path::new(file)) {
+            Some(found) => self.server_path(&found),
+            None => file.to_owned(),
+        };
+
+        match label {
+            Some(label) => format!("{}#{}", file, label),
+            None => file,
+        }
+    }
+}
+
+impl AssetTree for AssetPacks {
+    fn files(&self, folder: &str) -> Vec<(PathBuf, PathBuf)> {
+        AssetPacks::files(self, folder)
+    }
+
+    fn find(&self, path: &Path) -> Option<PathBuf> {
+        AssetPacks::find(self, path)
+    }
+}
+
+impl Display for AssetPacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self.packs.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        write!(f, "{}", names.join(" < "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::{Path, PathBuf}};
+
+    use crate::util::test_files::{temp_path, write};
+
+    use super::{AssetPack, AssetPacks};
+
+    #[test]
+    fn later_packs_override_files() {
+        //ARRANGE
+        let dir = temp_path("later_packs_override_files");
+        let base = dir.join("assets");
+        let pack = dir.join("packs/team");
+        write(&base.join("materials/wall/mod.json"), "{}");
+        write(&base.join("materials/wall/granite.json"), "{}");
+        write(&pack.join("materials/wall/granite.json"), "{}");
+        write(&pack.join("materials/wall/marble.json"), "{}");
+
+        let packs = AssetPacks::new(vec![
+            AssetPack { name: "assets".to_owned(), root: base.clone() },
+            AssetPack { name: "packs/team".to_owned(), root: pack.clone() },
+        ]);
+
+        //ACT
+        let files = packs.files("materials/wall");
+        let found = packs.find(Path::new("materials/wall/mod.json"));
+        let labelled = packs.load_path("materials/wall/marble.json#Texture0");
+
+        //ASSERT
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, vec![
+            (PathBuf::from("materials/wall/granite.json"), pack.join("materials/wall/granite.json")),
+            (PathBuf::from("materials/wall/marble.json"), pack.join("materials/wall/marble.json")),
+            (PathBuf::from("materials/wall/mod.json"), base.join("materials/wall/mod.json")),
+        ]);
+        assert_eq!(found, Some(base.join("materials/wall/mod.json")));
+        assert_eq!(packs.server_path(&base.join("textures/a.png")), "textures/a.png");
+        assert_eq!(labelled, format!("{}#Texture0", pack.join("materials/wall/marble.json").display()));
+    }
+
+    #[test]
+    fn display_lists_packs_in_override_order() {
+        //ARRANGE
+        let dir = temp_path("display_lists_packs_in_override_order");
+        let base = dir.join("assets");
+        let first = dir.join("packs/first");
+        let second = dir.join("packs/second");
+        write(&base.join("textures/stone.png"), "");
+        write(&first.join("textures/stone.png"), "");
+        write(&second.join("textures/stone.png"), "");
+
+        let packs = AssetPacks::new(vec![
+            AssetPack { name: "assets".to_owned(), root: base },
+            AssetPack { name: "packs/first".to_owned(), root: first },
+            AssetPack { name: "packs/second".to_owned(), root: second.clone() },
+        ]);
+
+        //ACT
+        let listed = packs.to_string();
+        let found = packs.find(Path::new("textures/stone.png"));
+
+        //ASSERT
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(listed, "assets < packs/first < packs/second");
+        assert_eq!(found, Some(second.join("textures/stone.png")));
+    }
+}
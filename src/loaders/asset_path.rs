@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use bevy::asset::FileAssetIo;
+
+/// Folder the asset server loads from, next to the executable or the manifest when started by cargo.
+pub const ASSET_FOLDER: &str = "assets";
+
+///
+/// Folder holding the asset folder and the pack config, the viewer works no matter which directory it is started from.
+pub fn base_path() -> PathBuf {
+    FileAssetIo::get_base_path()
+}
+
+pub fn asset_root() -> PathBuf {
+    base_path().join(ASSET_FOLDER)
+}
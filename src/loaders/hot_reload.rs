@@ -0,0 +1,112 @@
+use std::{path::{Path, PathBuf}, sync::{mpsc::{channel, Receiver}, Mutex}, time::{Duration, Instant}};
+
+use bevy::prelude::{info, warn, App, AssetServer, Res, ResMut, Resource, State};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::{asset_packs::AssetPacks, model_loader::ModelLoadingState};
+
+/// Editors tend to write a file in several steps, so reloading waits until the files have been quiet for this long.
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+const WATCHED_EXTENSIONS: [&str; 7] = ["json", "obj", "mtl", "gltf", "glb", "bin", "png"];
+
+/// Files the asset server loads textures from, they are cached by the server and have to be reloaded explicitly.
+const TEXTURE_EXTENSIONS: [&str; 3] = ["png", "gltf", "glb"];
+
+///
+/// Watches the asset packs and collects the model files and textures changed since the last reload.
+#[derive(Resource)]
+pub struct ModelWatcher {
+    _watcher: Mutex<RecommendedWatcher>,
+    events: Mutex<Receiver<PathBuf>>,
+    changed: Vec<PathBuf>,
+    last_change: Option<Instant>,
+}
+
+impl ModelWatcher {
+    pub fn new(roots: &[&Path]) -> notify::Result<Self> {
+        let (sender, receiver) = channel();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>|{
+            let Ok(event) = event else{
+                return;
+            };
+            if let EventKind::Access(_) = event.kind{
+                return;
+            }
+            for path in event.paths{
+                let _ = sender.send(path);
+            }
+        })?;
+        for root in roots{
+            watcher.watch(root, RecursiveMode::Recursive)?;
+        }
+
+        Ok(Self {
+            _watcher: Mutex::new(watcher),
+            events: Mutex::new(receiver),
+            changed: Vec::new(),
+            last_change: None,
+        })
+    }
+}
+
+pub fn add_hot_reload(app: &mut App, packs: &AssetPacks) {
+    let roots = packs.packs().iter().map(|x| x.root.as_path()).collect::<Vec<_>>();
+    match ModelWatcher::new(&roots){
+        Ok(watcher) => {
+            app
+                .insert_resource(watcher)
+                .add_system(reload_changed_models);
+        },
+        Err(err) => warn!("hot reloading disabled, couldn't watch {}: {}", packs, err),
+    }
+}
+
+fn is_watched(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .map_or(false, |x| WATCHED_EXTENSIONS.contains(&x))
+}
+
+///
+/// Starts the model loading over once the changed files have settled.
+/// Everything is parsed again, so inherit chains pick up changes to the files they depend on,
+/// and baking rebuilds the atlas and tells the world to remesh.
+fn reload_changed_models(
+    mut watcher: ResMut<ModelWatcher>,
+    mut state: ResMut<State<ModelLoadingState>>,
+    asset_server: Res<AssetServer>,
+    packs: Res<AssetPacks>,
+){
+    let watcher = &mut *watcher;
+
+    for path in watcher.events.get_mut().unwrap().try_iter(){
+        if is_watched(&path){
+            watcher.changed.push(path);
+            watcher.last_change = Some(Instant::now());
+        }
+    }
+
+    let Some(last_change) = watcher.last_change else{
+        return;
+    };
+
+    //A reload while the models are still loading would be picked up halfway, wait for it to finish
+    if last_change.elapsed() < SETTLE_TIME || *state.current() != ModelLoadingState::Baking{
+        return;
+    }
+
+    let mut changed = std::mem::take(&mut watcher.changed);
+    watcher.last_change = None;
+    changed.sort();
+    changed.dedup();
+
+    //The asset server keeps its loaded images, changed textures have to be read again before the atlas is rebuilt
+    for path in changed.iter().filter(|x| x.extension().and_then(|x| x.to_str()).map_or(false, |x| TEXTURE_EXTENSIONS.contains(&x))){
+        asset_server.reload_asset(packs.server_path(path).as_str());
+    }
+
+    info!("reloading models, {} files changed", changed.len());
+    state.set(ModelLoadingState::FileLoading).unwrap();
+}
@@ -0,0 +1,67 @@
+use std::{fmt::Display, path::{Path, PathBuf}};
+
+use model_system::loading::{error::ModelError, load_models};
+
+use super::{
+    asset_packs::AssetPacks,
+    asset_path::base_path,
+    model_loader::{BUILDING_ASSETS, CREATURE_ASSETS, MATERIAL_ASSETS},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Problem {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+///
+/// Runs every model folder through the same loading as the viewer, collecting every problem instead of stopping at the first.
+/// Files overridden by a later pack are not checked, the same way they are not loaded.
+pub fn lint_assets(packs: &AssetPacks) -> Vec<Problem> {
+    //Reported paths are shortened to start at the asset folder or pack.
+    let base = base_path();
+    let mut problems = Vec::new();
+
+    for folder in [MATERIAL_ASSETS, BUILDING_ASSETS, CREATURE_ASSETS] {
+        if !packs.has_folder(folder) {
+            problems.push(Problem {
+                path: PathBuf::from(folder),
+                line: None,
+                message: "missing model folder".to_owned(),
+            });
+            continue;
+        }
+
+        let (_, unreadable) = packs.walk(folder);
+        problems.extend(unreadable.into_iter().map(|message| Problem {
+            path: PathBuf::from(folder),
+            line: None,
+            message,
+        }));
+
+        let (_, errors) = load_models(packs, folder);
+        problems.extend(errors.iter().map(|x| into_problem(x, &base)));
+    }
+
+    problems.sort();
+    problems.dedup();
+    problems
+}
+
+fn into_problem(error: &ModelError, base: &Path) -> Problem {
+    Problem {
+        path: error.path.strip_prefix(base).unwrap_or(&error.path).to_owned(),
+        line: error.span.map(|x| x.line),
+        message: error.cause.to_string(),
+    }
+}
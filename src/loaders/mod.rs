@@ -1,9 +1,14 @@
-use bevy::prelude::{App, Res, ResMut, State, SystemSet, Resource};
+use bevy::prelude::{warn, App, Res, ResMut, State, SystemSet, Resource};
 
 use crate::AppState;
 
-use self::model_loader::add_model_loading;
+use self::{asset_packs::AssetPacks, hot_reload::add_hot_reload, model_loader::add_model_loading};
 
+pub mod asset_packs;
+pub mod asset_path;
+pub mod hot_reload;
+pub mod lint;
+pub mod model_cache;
 pub mod model_loader;
 
 const NUM_LOADERS: u32 = 1;
@@ -14,7 +19,14 @@ struct LoadingInfo {
 }
 
 pub fn add_loading_methods(app: &mut App) -> &mut App {
+    let (packs, problems) = AssetPacks::load();
+    for problem in problems {
+        warn!("{}", problem);
+    }
+    add_hot_reload(app, &packs);
+
     app
+        .insert_resource(packs)
         .insert_resource(LoadingInfo { loaded: 0 })
         .add_system_set(SystemSet::on_update(AppState::Setup)
             .with_system(check_done_loading));
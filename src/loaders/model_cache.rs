@@ -0,0 +1,290 @@
+use std::{error::Error, fs::{self, File}, hash::Hasher, io::{BufReader, BufWriter}, path::{Path, PathBuf}};
+
+use bevy::{prelude::{warn, Image}, render::render_resource::{Extent3d, TextureDimension, TextureFormat}};
+use serde::{Deserialize, Serialize};
+
+use crate::{voxel::{model_storage::{ModelRegistry, ModelStorage}, ModelEntry}, world::tile::material_identifier::{Identifier, MaterialIdentifierElement}};
+
+use super::{asset_packs::AssetPacks, asset_path::base_path, model_loader::SHAPES};
+
+/// Next to the asset folder, so writing the cache doesn't wake up the hot reloading.
+const CACHE_FOLDER: &str = "model_cache";
+
+/// Part of the key, has to be bumped whenever baking or the cached types change, the asset files alone can't tell.
+const CACHE_VERSION: u64 = 6;
+
+/// The atlas builder always packs into this format.
+const ATLAS_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+///
+/// Models of a storage by the elements of their identifier, in the order they were added.
+type CachedStorage<M> = Vec<(Vec<String>, M)>;
+
+#[derive(Serialize, Deserialize)]
+struct CachedModels<M>{
+    atlas_size: (u32, u32),
+    /// In the order of [`SHAPES`].
+    shapes: Vec<CachedStorage<M>>,
+    buildings: CachedStorage<M>,
+    creatures: CachedStorage<M>,
+}
+
+///
+/// A registry and atlas baked by an earlier run from the same asset tree.
+pub struct CachedBake{
+    pub registry: ModelRegistry,
+    pub atlas: Image,
+}
+
+///
+/// FNV-1a, unlike the std hashers it gives the same key on every run and Rust version.
+struct Fnv(u64);
+
+impl Hasher for Fnv{
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for x in bytes{
+            self.0 ^= *x as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+///
+/// Hash of every file in the asset tree as the loader sees it, by its path inside the tree and the contents of the pack file that wins.
+pub fn tree_key(packs: &AssetPacks) -> u64{
+    let mut hasher = Fnv(0xcbf29ce484222325);
+    hasher.write_u64(CACHE_VERSION);
+
+    for (path, file) in packs.files(""){
+        let contents = match fs::read(&file){
+            Ok(x) => x,
+            Err(err) => {
+                warn!("can't read {} for the model cache key: {}", file.display(), err);
+                continue;
+            },
+        };
+
+        hasher.write(path.to_string_lossy().as_bytes());
+        hasher.write_u64(contents.len() as u64);
+        hasher.write(&contents);
+    }
+
+    hasher.finish()
+}
+
+fn cache_folder() -> PathBuf{
+    base_path().join(CACHE_FOLDER)
+}
+
+fn cache_files(folder: &Path, key: u64) -> (PathBuf, PathBuf){
+    (folder.join(format!("{:016x}.ron", key)), folder.join(format!("{:016x}.rgba", key)))
+}
+
+///
+/// Whether a file is one [`cache_files`] names, for some key.
+fn is_cache_file(path: &Path) -> bool{
+    let stem = path.file_stem().and_then(|x| x.to_str()).unwrap_or("");
+    let extension = path.extension().and_then(|x| x.to_str()).unwrap_or("");
+    stem.len() == 16 && stem.chars().all(|x| x.is_ascii_hexdigit()) && (extension == "ron" || extension == "rgba")
+}
+
+///
+/// The cached bake for `key`, None if there isn't one or it can't be read.
+pub fn read_cache(key: u64) -> Option<CachedBake>{
+    let (models_file, atlas_file) = cache_files(&cache_folder(), key);
+    if !models_file.is_file(){
+        return None;
+    }
+
+    match read_files(models_file, atlas_file){
+        Ok(x) => Some(x),
+        Err(err) => {
+            warn!("ignoring the model cache: {}", err);
+            None
+        },
+    }
+}
+
+fn read_files(models_file: PathBuf, atlas_file: PathBuf) -> Result<CachedBake, Box<dyn Error>>{
+    let cached: CachedModels<ModelEntry> = ron::de::from_reader(BufReader::new(File::open(models_file)?))?;
+    let data = fs::read(atlas_file)?;
+
+    let (width, height) = cached.atlas_size;
+    if data.len() != width as usize * height as usize * 4{
+        return Err(format!("atlas has {} bytes, expected {}x{} pixels", data.len(), width, height).into());
+    }
+    if cached.shapes.len() != SHAPES.len(){
+        return Err(format!("cache has {} shapes, expected {}", cached.shapes.len(), SHAPES.len()).into());
+    }
+
+    let mut registry = ModelRegistry::new();
+    for ((_, shape), storage) in SHAPES.iter().zip(cached.shapes){
+        *registry.get_storage_entry_mut(*shape) = into_storage(storage);
+    }
+    *registry.get_building_storage_mut() = into_storage(cached.buildings);
+    *registry.get_creature_storage_mut() = into_storage(cached.creatures);
+
+    let atlas = Image::new(
+        Extent3d{ width, height, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        data,
+        ATLAS_FORMAT,
+    );
+
+    Ok(CachedBake{ registry, atlas })
+}
+
+fn into_storage(cached: CachedStorage<ModelEntry>) -> ModelStorage{
+    let mut storage = ModelStorage::new();
+    for (elements, model) in cached{
+        let identifier = Identifier::from(elements.into_iter().map(MaterialIdentifierElement::from).collect::<Vec<_>>());
+        storage.add_model(model, identifier);
+    }
+    storage
+}
+
+fn from_storage(storage: &ModelStorage) -> CachedStorage<&ModelEntry>{
+    storage.iter()
+        .map(|(identifier, model)| (identifier.elements().iter().map(|x| x.as_str().to_owned()).collect(), model))
+        .collect()
+}
+
+///
+/// Replaces the cache with a freshly baked registry, only the bake of the current asset tree is kept.
+/// Other files in the cache folder are left alone.
+pub fn write_cache(key: u64, registry: &ModelRegistry, atlas: &Image) -> Result<(), Box<dyn Error>>{
+    write_files(&cache_folder(), key, registry, atlas)
+}
+
+fn write_files(folder: &Path, key: u64, registry: &ModelRegistry, atlas: &Image) -> Result<(), Box<dyn Error>>{
+    if atlas.texture_descriptor.format != ATLAS_FORMAT{
+        return Err(format!("atlas format {:?} can't be cached", atlas.texture_descriptor.format).into());
+    }
+
+    let size = atlas.texture_descriptor.size;
+    let cached = CachedModels{
+        atlas_size: (size.width, size.height),
+        shapes: SHAPES.iter().map(|(_, shape)| from_storage(registry.get_storage_entry(*shape))).collect(),
+        buildings: from_storage(registry.get_building_storage()),
+        creatures: from_storage(registry.get_creature_storage()),
+    };
+
+    fs::create_dir_all(folder)?;
+
+    //Bakes of older asset trees are never read again, anything else in the folder isn't ours to delete
+    let (models_file, atlas_file) = cache_files(folder, key);
+    for entry in fs::read_dir(folder)?{
+        let path = entry?.path();
+        if is_cache_file(&path) && path != models_file && path != atlas_file{
+            fs::remove_file(path)?;
+        }
+    }
+
+    fs::write(atlas_file, &atlas.data)?;
+    ron::ser::to_writer(BufWriter::new(File::create(models_file)?), &cached)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs};
+
+    use bevy::{prelude::{Image, Vec2, Vec3}, render::render_resource::{Extent3d, TextureDimension}};
+
+    use crate::{
+        loaders::{asset_packs::{AssetPack, AssetPacks}, model_loader::{BakedModel, Cullable}},
+        voxel::{model_storage::ModelRegistry, ModelData, ModelEntry},
+        util::test_files::{temp_path, write},
+        world::tile::material_identifier::Identifier,
+    };
+
+    use super::{cache_files, read_files, tree_key, write_files, ATLAS_FORMAT};
+
+    #[test]
+    fn tree_key_changes_only_with_the_files() {
+        //ARRANGE
+        let dir = temp_path("tree_key_changes_only_with_the_files");
+        write(&dir.join("materials/mod.json"), "{}");
+        let packs = AssetPacks::new(vec![AssetPack { name: "assets".to_owned(), root: dir.clone() }]);
+
+        //ACT
+        let first = tree_key(&packs);
+        let again = tree_key(&packs);
+        write(&dir.join("materials/mod.json"), "{\"tint\": true}");
+        let changed = tree_key(&packs);
+
+        //ASSERT
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(first, again);
+        assert_ne!(first, changed);
+    }
+
+    #[test]
+    fn written_cache_reads_back() {
+        //ARRANGE
+        let dir = temp_path("written_cache_reads_back");
+        let mut registry = ModelRegistry::new();
+        registry.get_building_storage_mut().add_model(ModelEntry(ModelData {
+            models: vec![BakedModel::Quad {
+                verts: [Vec3::ZERO, Vec3::X, Vec3::ONE, Vec3::Y],
+                uvs: [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y],
+                normal: Vec3::Z,
+                cullable: Cullable::Never,
+            }],
+            transparent: true,
+            tint: false,
+            visibility: BTreeMap::new(),
+        }), Identifier::from("WORKSHOP:STONE".to_owned()));
+        let atlas = Image::new(
+            Extent3d { width: 2, height: 1, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            vec![1, 2, 3, 4, 5, 6, 7, 8],
+            ATLAS_FORMAT,
+        );
+
+        //ACT
+        write_files(&dir, 7, &registry, &atlas).unwrap();
+        let (models_file, atlas_file) = cache_files(&dir, 7);
+        let cached = read_files(models_file, atlas_file);
+
+        //ASSERT
+        fs::remove_dir_all(&dir).unwrap();
+        let cached = cached.unwrap();
+        let buildings = cached.registry.get_building_storage().iter().collect::<Vec<_>>();
+        assert_eq!(buildings.len(), 1);
+        assert_eq!(buildings[0].0.elements().iter().map(|x| x.as_str()).collect::<Vec<_>>(), vec!["WORKSHOP", "STONE"]);
+        assert!(buildings[0].1.0.transparent);
+        assert_eq!(cached.atlas.data, atlas.data);
+        assert_eq!(cached.atlas.texture_descriptor.size, atlas.texture_descriptor.size);
+    }
+
+    #[test]
+    fn writing_the_cache_only_replaces_cache_files() {
+        //ARRANGE
+        let dir = temp_path("writing_the_cache_only_replaces_cache_files");
+        let (old_models, old_atlas) = cache_files(&dir, 3);
+        write(&old_models, "");
+        write(&old_atlas, "");
+        write(&dir.join("notes.txt"), "mine");
+        let atlas = Image::new(
+            Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            vec![1, 2, 3, 4],
+            ATLAS_FORMAT,
+        );
+
+        //ACT
+        write_files(&dir, 7, &ModelRegistry::new(), &atlas).unwrap();
+        let (models_file, atlas_file) = cache_files(&dir, 7);
+        let kept = [old_models.is_file(), old_atlas.is_file(), dir.join("notes.txt").is_file(), models_file.is_file(), atlas_file.is_file()];
+
+        //ASSERT
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(kept, [false, false, true, true, true]);
+    }
+}
@@ -1,27 +1,47 @@
-use std::{path::PathBuf, fs, str::FromStr, hash::Hash};
-
-use bevy::{utils::{HashMap, HashSet}, prelude::{Vec2, Vec3, IVec4, ResMut, AssetServer, Res, Assets, Image, Handle, Resource, App, SystemSet, State, warn, StandardMaterial, default, AlphaMode}, asset::LoadState, sprite::{TextureAtlasBuilder, TextureAtlas}};
+use bevy::{prelude::{Vec2, Vec3, IVec4, EventWriter, info, ResMut, AssetServer, Res, Assets, Image, Handle, Resource, App, SystemSet, State, warn, StandardMaterial, default, AlphaMode}, asset::LoadState, sprite::{TextureAtlasBuilder, TextureAtlas}};
 use df_rust::clients::remote_fortress_reader::remote_fortress_reader::TiletypeShape;
+use model_system::{baking::{self, quad, BakedModels, BakedPart, Geometry}, format::{Cull, Shape, Side, Visibility}, naming::MaterialIdentifier};
 use serde::{Deserialize, Serialize};
-use walkdir::WalkDir;
-
-use crate::{world::{tile::material_identifier::{MaterialIdentifierElement, Identifier}, events::chunk_builder::VOXEL_MATERIAL}, voxel::{model_storage::{ModelStorage, ModelRegistry, RegistryContainers}, ModelEntry, ModelData}, util::mesh_loader::load_mesh_file};
-
-use super::LoadingInfo;
 
-const SHAPE_ASSETS: [(&str, TiletypeShape);4] = [
-    ("assets/materials/wall",TiletypeShape::Wall),
-    ("assets/materials/floor",TiletypeShape::Floor),
-    ("assets/materials/up_down_stair",TiletypeShape::StairUpdown),
-    ("assets/materials/down_stair",TiletypeShape::StairDown),
+use crate::{world::{tile::material_identifier::{MaterialIdentifierElement, Identifier}, events::chunk_builder::{VOXEL_MATERIAL, TRANSPARENT_MATERIAL}}, voxel::{model_storage::{ModelStorage, ModelRegistry}, ModelEntry, ModelData}};
+
+use super::{asset_packs::AssetPacks, model_cache::{read_cache, tree_key, write_cache, CachedBake}, LoadingInfo};
+
+/// Tile shapes of the model files, in the order the registry is cached in
+pub(super) const SHAPES: [(Shape, TiletypeShape);21] = [
+    (Shape::NoShape,TiletypeShape::NoShape),
+    (Shape::Empty,TiletypeShape::Empty),
+    (Shape::Floor,TiletypeShape::Floor),
+    (Shape::Boulder,TiletypeShape::Boulder),
+    (Shape::Pebbles,TiletypeShape::Pebbles),
+    (Shape::Wall,TiletypeShape::Wall),
+    (Shape::Fortification,TiletypeShape::Fortification),
+    (Shape::UpStair,TiletypeShape::StairUp),
+    (Shape::DownStair,TiletypeShape::StairDown),
+    (Shape::UpDownStair,TiletypeShape::StairUpdown),
+    (Shape::Ramp,TiletypeShape::Ramp),
+    (Shape::RampTop,TiletypeShape::RampTop),
+    (Shape::BrookBed,TiletypeShape::BrookBed),
+    (Shape::BrookTop,TiletypeShape::BrookTop),
+    (Shape::Tree,TiletypeShape::TreeShape),
+    (Shape::Sapling,TiletypeShape::Sapling),
+    (Shape::Shrub,TiletypeShape::Shrub),
+    (Shape::EndlessPit,TiletypeShape::EndlessPit),
+    (Shape::Branch,TiletypeShape::Branch),
+    (Shape::TrunkBranch,TiletypeShape::TrunkBranch),
+    (Shape::Twig,TiletypeShape::Twig),
 ];
 
+/// Model folders inside the asset tree, materials list their models by tile shape
+pub(super) const MATERIAL_ASSETS: &str = "materials";
+pub(super) const BUILDING_ASSETS: &str = "buildings";
+pub(super) const CREATURE_ASSETS: &str = "creatures";
 
-#[derive(Deserialize)]
-pub struct Texturing<'a>{
-    src: &'a str,
-    clip: Option<(u32,u32,u32,u32)>,
-}
+/// Stands in for textures that can't be resolved or loaded, so a broken asset shows up instead of crashing the viewer
+pub const MISSING_TEXTURE: &str = "textures/missing.png";
+
+/// Room for a few full size textures embedded in glTF files, and still within what GPUs support
+const ATLAS_MAX_SIZE: f32 = 8192.0;
 
 #[derive(Deserialize, Serialize,Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum Direction{
@@ -45,6 +65,17 @@ impl Direction{
         }
     }
 
+    pub const fn opposite(self) -> Direction{
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Forward => Direction::Backwards,
+            Direction::Backwards => Direction::Forward,
+        }
+    }
+
     pub const fn get_bit(self) -> u8{
         match self {
             Direction::Up =>        0b00100000,
@@ -68,34 +99,30 @@ impl Direction{
     }
 }
 
-#[derive(Deserialize)]
-pub enum MeshElement<'a>{
-    Params(
-        HashMap<&'a str, &'a str>
-    ),
-    Inherit(&'a str),
-    Transparent(bool),
-    Face{
-        n: Direction,
-        s: Option<(f32, f32)>,
-        p: Option<(f32, f32, f32)>,
-        r: Option<f32>,
-        t: Texturing<'a>,
-        #[serde(default = "Cullable::default")]
-        cullable: Cullable
-    },
-    MeshImport{
-        src: &'a str,
-        t: Texturing<'a>,
-        cullable: Cullable,
-    },
-    Mesh{
-        verts: Vec<(f32,f32,f32)>,
-        uvs: Vec<(f32, f32)>,
-        normals: Option<Vec<(f32,f32,f32)>>,
-        indices: Vec<(u16,u16,u16)>,
-        t: Texturing<'a>,
-    },
+impl From<Direction> for Side{
+    fn from(x: Direction) -> Self {
+        match x{
+            Direction::Up => Side::Up,
+            Direction::Down => Side::Down,
+            Direction::Left => Side::Left,
+            Direction::Right => Side::Right,
+            Direction::Forward => Side::Forward,
+            Direction::Backwards => Side::Backwards,
+        }
+    }
+}
+
+impl From<Side> for Direction{
+    fn from(x: Side) -> Self {
+        match x{
+            Side::Up => Direction::Up,
+            Side::Down => Direction::Down,
+            Side::Left => Direction::Left,
+            Side::Right => Direction::Right,
+            Side::Forward => Direction::Forward,
+            Side::Backwards => Direction::Backwards,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Copy)]
@@ -119,31 +146,22 @@ impl Cullable{
     }
 }
 
+impl From<Cull> for Cullable{
+    fn from(x: Cull) -> Self {
+        match x{
+            Cull::Never => Cullable::Never,
+            Cull::WhenVisible(side) => Cullable::WhenVisible(side.into()),
+            Cull::WhenHidden(side) => Cullable::WhenHidden(side.into()),
+        }
+    }
+}
+
 impl Default for Cullable {
     fn default() -> Self {
         Self::Never
     }
 }
 
-#[derive(Debug)]
-pub enum PreBakedModel{
-    Face{
-        n: Direction,
-        s: Vec2,
-        p: Vec3,
-        r: f32,
-        t: (i32, Option<IVec4>),
-        cullable: Cullable,
-    },
-    Mesh{
-        verts: Vec<Vec3>,
-        uvs: Vec<Vec2>,
-        normals: Vec<Vec3>,
-        indices: Vec<u16>,
-        t: (i32, Option<IVec4>),
-        cullable: Cullable,
-    }
-}
 #[derive(Debug, Serialize, Deserialize)]
 pub enum BakedModel{
     Quad{
@@ -155,6 +173,9 @@ pub enum BakedModel{
 
     Mesh{
         data: Vec<(Vec3, Vec2, Vec3)>,
+        /// Linear rgba per vertex, multiplied into the model colour
+        #[serde(default)]
+        colors: Option<Vec<[f32; 4]>>,
         indices: Vec<u16>,
         cullable: Cullable
     }
@@ -167,29 +188,54 @@ impl BakedModel{
             BakedModel::Mesh {cullable,.. } => *cullable,
         }
     }
+
+    ///
+    /// Smallest (min, max) rect in the atlas covering every uv of this part.
+    pub fn uv_bounds(&self) -> (Vec2, Vec2){
+        let mut uvs: Box<dyn Iterator<Item = Vec2> + '_> = match self{
+            BakedModel::Quad { uvs, .. } => Box::new(uvs.iter().copied()),
+            BakedModel::Mesh { data, .. } => Box::new(data.iter().map(|x| x.1)),
+        };
+        let first = uvs.next().unwrap_or(Vec2::ZERO);
+        uvs.fold((first, first), |(min, max), x| (min.min(x), max.max(x)))
+    }
 }
 
-pub type ModelCache = (Vec<PathBuf>, HashMap<PathBuf,(Vec<PreBakedModel>,bool)>);
+type BakedEntries = Vec<(MaterialIdentifier, baking::BakedModel)>;
 
 #[derive(Resource, Default)]
 pub struct ModelLoadingData{
-    
-    chache_storage: RegistryContainers<ModelCache>,
+    /// Tile models of every material by shape
+    materials: BakedModels,
+    buildings: BakedEntries,
+    creatures: BakedEntries,
 
     pub atlas_handle: Handle<Image>,
+    /// Asset paths of the textures in `handlers`, the missing texture first
+    textures: Vec<String>,
     handlers: Vec<Handle<Image>>,
+
+    /// Content hash of the asset tree the models are loaded from.
+    tree_key: u64,
+    /// Set instead of the models above when an earlier run baked the same asset tree.
+    cached: Option<CachedBake>,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
-enum ModelLoadingState{
+pub(super) enum ModelLoadingState{
     FileLoading,
     Baking,
 }
 
+///
+/// Sent every time the models have been baked into a new [`ModelRegistry`], everything meshed with the old one has to be rebuilt.
+pub struct ModelsBaked;
+
 pub fn add_model_loading(
     app: &mut App
 ){
     app
+    .add_event::<ModelsBaked>()
     .add_state(ModelLoadingState::FileLoading)
     .insert_resource(ModelLoadingData::default())
     .add_system_set(SystemSet::on_enter(ModelLoadingState::FileLoading)
@@ -205,71 +251,83 @@ fn check_textures(
     asset_server: Res<AssetServer>,
     model_data: Res<ModelLoadingData>,
 ){
-    if let LoadState::Loaded = asset_server.get_group_load_state(model_data.handlers.iter().map(|x|x.id())){
+    //Textures that failed are replaced by the missing texture when baking
+    let done = model_data.handlers
+        .iter()
+        .all(|x| matches!(asset_server.get_load_state(x), LoadState::Loaded | LoadState::Failed));
+
+    if done{
         state.set(ModelLoadingState::Baking).unwrap();
     }
 }
 
 fn load_models(
     asset_server: Res<AssetServer>,
+    packs: Res<AssetPacks>,
     mut model_data: ResMut<ModelLoadingData>,
 ){
-    let mut textures = Vec::new();
-
-
-    for (folder,shape) in &SHAPE_ASSETS{
-        *model_data.chache_storage.get_mut(*shape) = load_folder(folder, &mut textures, &asset_server);
+    info!("loading models from {}", *packs);
+
+    model_data.tree_key = tree_key(&packs);
+    if let Some(cached) = read_cache(model_data.tree_key){
+        info!("assets unchanged since the last run, using the cached models");
+        model_data.cached = Some(cached);
+        model_data.handlers = Vec::new();
+        return;
     }
 
-    let textures = textures.into_iter().map(|x| asset_server.load::<Image,_>(x)).collect::<Vec<_>>();
-    model_data.handlers = textures;
-}
-
-fn load_folder(path: &str, textures: &mut Vec<String>, asset_server: &AssetServer)-> (Vec<PathBuf>, bevy::utils::hashbrown::HashMap<PathBuf, (Vec<PreBakedModel>, bool)>) {
-    let mut file_cache = HashMap::new();
-
-    let mut materials = Vec::new();
-
-    for file in WalkDir::new(path){
-        let Ok(entry) = file else{
-            eprintln!("{}",file.unwrap_err());
-            continue;
-        };
-
-        if entry.file_type().is_file(){
-            let path = entry.path();
-            materials.push(path.to_owned());
-            if !file_cache.contains_key(path){
-                load_model(&mut file_cache, entry.into_path())
-            }
-            else{
-                println!("path already loaded! {:?}",path);
+    model_data.materials = load_folder(&packs, MATERIAL_ASSETS);
+    model_data.buildings = load_folder(&packs, BUILDING_ASSETS).models;
+    model_data.creatures = load_folder(&packs, CREATURE_ASSETS).models;
+    report_missing_shapes(&model_data.materials);
+
+    //Always loaded first, parts without a texture fall back to the first handle
+    let mut textures = vec![MISSING_TEXTURE.to_owned()];
+    let models = SHAPES.iter()
+        .flat_map(|(shape, _)| model_data.materials.shapes.get(*shape))
+        .chain(&model_data.buildings)
+        .chain(&model_data.creatures);
+    for (_, model) in models{
+        for texture in model.parts.iter().filter_map(|x| x.texture.as_ref()){
+            if !textures.contains(texture){
+                textures.push(texture.clone());
             }
         }
     }
 
-    let file_cache = file_cache.into_iter().map(|(path, (vars, mut models, transparent))|{
-        for x in &mut models{
-            match x {
-                PreBakedModel::Face { n, s, p, r, t, cullable } => {
-                    let texture = solve_variable(&vars, textures, t.0 as usize);
-                    t.0 = texture as i32;
-                },
-                PreBakedModel::Mesh { verts, uvs, normals, indices, t, cullable } => {
-                    let texture = solve_variable(&vars, textures, t.0 as usize);
-                    t.0 = texture as i32;    
-                },
-            }
+    model_data.handlers = textures.iter().map(|x| asset_server.load::<Image,_>(packs.load_path(x))).collect();
+    model_data.textures = textures;
+}
+
+///
+/// Bakes every model file in `folder`, broken models are left out after their problems are logged.
+fn load_folder(packs: &AssetPacks, folder: &str) -> BakedModels{
+    let (models, problems) = model_system::loading::load_models(packs, folder);
+    if !problems.is_empty(){
+        warn!("{} problems in {}:", problems.len(), folder);
+        for problem in &problems{
+            warn!("    {}", problem);
         }
+    }
+    models
+}
 
-        (path, (models, transparent))
-    }).collect::<HashMap<PathBuf,(Vec<PreBakedModel>, bool)>>();
+///
+/// Without a model in the root mod.json a shape has no default model, so every tile of that shape without a more specific model is skipped.
+fn report_missing_shapes(materials: &BakedModels){
+    let missing = SHAPES
+        .iter()
+        .filter(|(shape, _)| !materials.shapes.get(*shape).iter().any(|(identifier, _)| identifier.elements().is_empty()))
+        .collect::<Vec<_>>();
 
-    println!("{:#?}",textures);
-    println!("{:#?}",file_cache);
+    if missing.is_empty(){
+        return;
+    }
 
-    
-    (materials, file_cache)
+    warn!("{} shapes have no default model in {}/mod.json:", missing.len(), MATERIAL_ASSETS);
+    for (shape, _) in missing{
+        warn!("    {:?} ({})", shape, shape.name());
+    }
 }
 
 pub(super) fn bake_models(
@@ -279,11 +337,52 @@ pub(super) fn bake_models(
     mut textures: ResMut<Assets<Image>>,
     mut registry: ResMut<ModelRegistry>,
     mut info: ResMut<LoadingInfo>,
-    mut materials: ResMut<Assets<StandardMaterial>>
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut baked: EventWriter<ModelsBaked>,
 ){
-    let mut atlas_builder = TextureAtlasBuilder::default();
+    let old_atlas = model_data.atlas_handle.clone();
+    let new_registry = match model_data.cached.take(){
+        Some(cached) => {
+            model_data.atlas_handle = textures.add(cached.atlas);
+            cached.registry
+        },
+        None => bake_registry(&asset_server, &mut model_data, &mut textures),
+    };
+
+    //Meshing tasks may still hold on to the current registry, so the new one is swapped in
+    *registry = new_registry;
+
+    materials.set_untracked(VOXEL_MATERIAL, StandardMaterial{
+        base_color_texture: Some(model_data.atlas_handle.clone()),
+        metallic: 0.0,
+        reflectance: 0.0,
+        alpha_mode: AlphaMode::Mask(0.5),
+        ..default()
+    });
+
+    materials.set_untracked(TRANSPARENT_MATERIAL, StandardMaterial{
+        base_color_texture: Some(model_data.atlas_handle.clone()),
+        metallic: 0.0,
+        reflectance: 0.0,
+        alpha_mode: AlphaMode::Blend,
+        ..default()
+    });
+
+    //Nothing but the materials above uses the atlas, the one from before a reload would stay loaded forever
+    if old_atlas != model_data.atlas_handle{
+        textures.remove(&old_atlas);
+    }
+
+    info.loaded += 1;
+    baked.send(ModelsBaked);
+}
+
+///
+/// Packs the loaded textures into a new atlas and bakes the loaded models against it, the result is cached for the next run.
+fn bake_registry(asset_server: &AssetServer, model_data: &mut ModelLoadingData, textures: &mut Assets<Image>) -> ModelRegistry{
+    let mut atlas_builder = TextureAtlasBuilder::default().max_size(Vec2::splat(ATLAS_MAX_SIZE));
     for x in &model_data.handlers{
-        let Some(tex) = textures.get(&x) else{
+        let Some(tex) = textures.get(x) else{
             warn!("invalid image path {:?}",asset_server.get_handle_path(x));
             continue;
         };
@@ -291,77 +390,85 @@ pub(super) fn bake_models(
         atlas_builder.add_texture(x.clone_weak(), tex);
     }
 
-    let atlas = atlas_builder.finish(&mut textures).unwrap();
+    let atlas = match atlas_builder.finish(textures){
+        Ok(atlas) => atlas,
+        Err(err) => {
+            warn!("the textures don't fit into one atlas, every model uses the missing texture: {}", err);
+            let missing = &model_data.handlers[0];
+            let mut fallback = TextureAtlasBuilder::default();
+            if let Some(tex) = textures.get(missing){
+                fallback.add_texture(missing.clone_weak(), tex);
+            }
+            fallback.finish(textures).expect("the missing texture fits into an atlas")
+        },
+    };
     let atlas_texture = atlas.texture.clone();
 
     model_data.atlas_handle = atlas_texture;
 
-    for (folder,shape) in &SHAPE_ASSETS{
-        *registry.get_storage_entry_mut(*shape) = build_storage(
-                std::mem::take(model_data.chache_storage.get_mut(*shape)), 
-                &atlas, 
-                &model_data.handlers,
-                folder
-            );
+    let mut new_registry = ModelRegistry::new();
+    let lookup = TextureLookup{
+        atlas: &atlas,
+        textures: &model_data.textures,
+        handlers: &model_data.handlers,
+    };
 
-            println!("{:?}: {:#?}",shape,registry.get_storage_entry(*shape));
+    for (shape, tiletype) in &SHAPES{
+        let models = std::mem::take(model_data.materials.shapes.get_mut(*shape));
+        *new_registry.get_storage_entry_mut(*tiletype) = build_storage(models, &lookup);
     }
+    *new_registry.get_building_storage_mut() = build_storage(std::mem::take(&mut model_data.buildings), &lookup);
+    *new_registry.get_creature_storage_mut() = build_storage(std::mem::take(&mut model_data.creatures), &lookup);
 
-    materials.get_or_insert_with(VOXEL_MATERIAL.typed::<StandardMaterial>(), ||StandardMaterial{
-        base_color_texture: Some(model_data.atlas_handle.clone()),
-        metallic: 0.0,
-        reflectance: 0.0,
-        alpha_mode: AlphaMode::Mask(0.5),
-        ..default()
-    });
+    if let Some(image) = textures.get(&model_data.atlas_handle){
+        if let Err(err) = write_cache(model_data.tree_key, &new_registry, image){
+            warn!("couldn't write the model cache: {}", err);
+        }
+    }
 
-    info.loaded += 1;
+    new_registry
 }
 
-fn build_storage(cache: ModelCache, atlas: &TextureAtlas, handlers: &[Handle<Image>], path_root: &str) -> ModelStorage{
+///
+/// Where the textures of the model parts ended up in the atlas.
+struct TextureLookup<'a>{
+    atlas: &'a TextureAtlas,
+    textures: &'a [String],
+    handlers: &'a [Handle<Image>],
+}
 
-    let (entries, mut cache) = cache;
+impl TextureLookup<'_>{
+    fn uv_rect(&self, part: &BakedPart) -> (Vec2, Vec2){
+        let index = part.texture.as_ref()
+            .and_then(|x| self.textures.iter().position(|y| y == x))
+            .unwrap_or(0);
+        let clip = part.clip.map(|[x, y, z, w]| IVec4::new(x as i32, y as i32, z as i32, w as i32));
+        atlas_uv_rect(self.atlas, &self.handlers[index], &self.handlers[0], clip)
+    }
+}
 
+fn build_storage(models: BakedEntries, textures: &TextureLookup) -> ModelStorage{
     let mut storage = ModelStorage::new();
 
-    for path in entries{
-        let Some((model, transparent)) = cache.remove(&path) else{
-            panic!("{} does not have a model!",path.display());
-        };
+    for (identifier, model) in models{
         let mut quads = Vec::new();
 
-        for x in model{
-            match x{
-                PreBakedModel::Face { n, s, p, r, t, cullable } => {
-                    let (vs, us, normal,) = create_quad(n, s, p, r);
-
-                    let texture_handle = &handlers[t.0 as usize];
-                    let index = atlas.get_texture_index(texture_handle).unwrap();
-                    let rect = atlas.textures[index];
+        for part in model.parts{
+            let (off, size) = textures.uv_rect(&part);
+            let cullable = part.cull.into();
 
-                    let off = rect.min / atlas.size;
-                    let size = (rect.max - rect.min) / atlas.size;
-
-                    println!("{}: {:?} => {} {}",t.0, texture_handle, off, size);
-                    
-                    let uv = us.map(|x| x*size + off);
+            match part.geometry{
+                Geometry::Quad { verts, uvs, normal } => {
                     quads.push(BakedModel::Quad{
-                        verts: vs,
-                        uvs: uv,
+                        verts,
+                        uvs: uvs.map(|x| x*size + off),
                         normal,
                         cullable,
                     });
                 },
-                PreBakedModel::Mesh { verts, uvs, normals, indices, t, cullable } => {
-                    let texture_handle = &handlers[t.0 as usize];
-                    let index = atlas.get_texture_index(texture_handle).unwrap();
-                    let rect = atlas.textures[index];
-
-                    let off = rect.min / atlas.size;
-                    let size = (rect.max - rect.min) / atlas.size;
-
+                Geometry::Triangles { verts, uvs, normals, colors, indices } => {
                     let mut data = Vec::new();
-        
+
                     for ((vert, uv), normal) in verts.into_iter().zip(uvs.into_iter().map(|x| x*size + off)).zip(normals.into_iter()){
                         data.push((vert, uv, normal));
                     }
@@ -369,245 +476,64 @@ fn build_storage(cache: ModelCache, atlas: &TextureAtlas, handlers: &[Handle<Ima
                     quads.push(BakedModel::Mesh{
                         indices,
                         cullable,
+                        colors,
                         data
                     })
                 },
             }
         }
 
-        let name = into_material_name(path, path_root);
-        
+        let name = identifier.elements().iter().map(|x| MaterialIdentifierElement::from(x.as_str())).collect::<Vec<_>>();
 
         let data = ModelData{
-            transparent,
+            transparent: model.transparent,
+            tint: model.tint,
             models: quads,
+            visibility: Side::ALL
+                .into_iter()
+                .filter_map(|side| Some((side.into(), model.visibility.specified(side)? == Visibility::Solid)))
+                .collect(),
         };
 
-        storage.add_model(ModelEntry(data), name);
+        storage.add_model(ModelEntry(data), Identifier::from(name));
     }
     storage
 }
 
-fn into_material_name(path: PathBuf, root: &str) -> Identifier{
-
-    println!("{}",path.display());
-    let path = path.strip_prefix(root).unwrap();
-    let mut out = Vec::new();
-    for x in path.iter(){
-        let s = x.to_string_lossy();
-        if s != "mod.ron"{
-            let s = if s.ends_with(".ron"){
-                s.strip_suffix(".ron").unwrap().to_owned()
+///
+/// Offset and size of a texture in atlas uv space.
+/// `clip` picks a (x, y, width, height) pixel rect out of the source texture, for tilesets holding many tiles.
+/// Textures that failed to load are replaced by the whole `fallback` texture.
+fn atlas_uv_rect(atlas: &TextureAtlas, handle: &Handle<Image>, fallback: &Handle<Image>, clip: Option<IVec4>) -> (Vec2, Vec2){
+    let (index, clip) = match atlas.get_texture_index(handle){
+        Some(index) => (index, clip),
+        None => match atlas.get_texture_index(fallback){
+            Some(index) => (index, None),
+            None => return (Vec2::ZERO, Vec2::ZERO),
+        },
+    };
+    let rect = atlas.textures[index];
+
+    let (min, size) = match clip{
+        Some(clip) => {
+            let min = rect.min + Vec2::new(clip.x as f32, clip.y as f32);
+            let max = min + Vec2::new(clip.z as f32, clip.w as f32);
+            if max.cmpgt(rect.max).any(){
+                warn!("clip {} is outside of the texture {:?}, it is cut to fit", clip, rect.max - rect.min);
             }
-            else{
-                s.to_string()
-            };
-            out.push(MaterialIdentifierElement::from(s));
-        }
-    }
-
-    let id = out.into();
-    println!("{}",id);
-    id
-}
-
-fn create_quad(normal: Direction, size: Vec2, position: Vec3, rotation: f32) -> ([Vec3;4], [Vec2;4], Vec3){
-    let [x, y, z] = normal.get_coords();
-
-    let x = x/2.0;
-    let z = z/2.0;
-    let verts = [
-        -x*size.x + -z*size.y + position,
-         x*size.x + -z*size.y + position,
-        -x*size.x +  z*size.y + position,
-         x*size.x +  z*size.y + position,
-    ];
-
-    let uvs = [
-        Vec2::new(0.0, 0.0),
-        Vec2::new(1.0, 0.0),
-        Vec2::new(0.0, 1.0),
-        Vec2::new(1.0, 1.0),
-    ];
-    
-    (verts,uvs,y)
-}
-
-fn solve_variable(vars: &Vec<(String, String)>, textures: &mut Vec<String>, start: usize) -> usize{
-    let mut chain = HashSet::new();
-    let mut i = start;
-    let texture = loop{
-        
-        let (key, value) = &vars[i];
-        assert!(chain.insert(key),"cyclic reference found {:?}, duplicate key: {}",chain,key);
-        if value.starts_with("#"){
-            i = vars.iter().enumerate().find(|(_,(k,_))| k == &value[1..]).unwrap().0;
-        }
-        else{
-            break value;
-        }
+            //Sampling past the texture would show its neighbours in the atlas
+            let min = min.min(rect.max);
+            (min, max.min(rect.max) - min)
+        },
+        None => (rect.min, rect.max - rect.min),
     };
-    let texture_index = textures.iter().enumerate().find(|(_,k)|{
-        (*k).eq(texture)
-    }).map(|(k,_)|k).unwrap_or_else(|| {
-        textures.push(texture.clone());
-        textures.len()-1
-    });
 
-    texture_index
+    (min / atlas.size, size / atlas.size)
 }
 
-fn load_model(file_cache: &mut HashMap<PathBuf,(Vec<(String,String)>,Vec<PreBakedModel>, bool)>, path: PathBuf){
-    println!("reading {:?}",path.as_os_str());
-    let source = fs::read_to_string(&path).unwrap();
-    let raw_model: Vec<MeshElement> = ron::from_str(&source).unwrap();
-
-    let mut prebaked = Vec::new();
-
-    let mut variables = Vec::new();
-
-    let mut transparent = false;
-
-    for x in raw_model{
-        match x{
-            MeshElement::Transparent(t) => {
-                transparent = t;
-            }
-
-            MeshElement::Params(vars) => {
-                for (key, value) in vars{
-                    variables.push((key.to_owned(), value.to_owned()));
-                }
-            },
-            MeshElement::Inherit(src) => {
-                let inherit_path = PathBuf::from_str(src).unwrap();
-                if !file_cache.contains_key(&inherit_path){
-                    load_model(file_cache, inherit_path.clone());
-                }
-                let parent = file_cache.get(&inherit_path).unwrap();
-                'outer: for (key, value) in &parent.0{
-                    for (exsisting_key, _) in &variables{
-                        if key == exsisting_key{
-                            continue 'outer;
-                        }
-                    }
-
-                    variables.push((key.clone(), value.clone()));
-                }
-
-                for x in &parent.1{
-                    match x{
-                        PreBakedModel::Face { n, s, p, r, t, cullable } => {
-                            let var_key = &parent.0[t.0 as usize].0;
-                            let mut t = (t.0, t.1);
-                            if let Some ((index,_)) = variables.iter().enumerate().find(|(_, (key,_))| key == var_key){
-                                t.0 = index as i32;
-                            }
-                            
-                            prebaked.push(PreBakedModel::Face{
-                                n: *n,
-                                p: *p,
-                                r: *r,
-                                s: *s,
-                                t,
-                                cullable: *cullable
-                            });
-                        },
-                        PreBakedModel::Mesh { verts, uvs, normals, indices, t, cullable } => {
-                            let var_key = &parent.0[t.0 as usize].0;
-                            let mut t = (-1, t.1);
-                            if let Some ((index,_)) = variables.iter().enumerate().find(|(_, (key,_))| key == var_key){
-                                t.0 = index as i32;
-                            }
-
-                            prebaked.push(PreBakedModel::Mesh{
-                                indices: indices.clone(),
-                                normals: normals.clone(),
-                                uvs: uvs.clone(),
-                                verts: verts.clone(),
-                                t,
-                                cullable: *cullable
-                            });
-                        },
-                    }
-                }
-            },
-            MeshElement::Face { n, s, p, r, t , cullable} => {
-                let t = {
-                    let index = variables
-                        .iter()
-                        .enumerate()
-                        .find_map(|x| if x.1.0 == t.src {Some(x.0 as i32)} else {None})
-                        .unwrap_or_else(|| panic!("unreqognized variable {}",t.src));
-
-                    (index,t.clip.map(|(x, y, z, w)| IVec4::new(x as i32, y as i32, z as i32, w as i32)))
-                };
-
-                let n = n;
-                let s = Vec2::from(s.unwrap_or((1.0,1.0)));
-                let p = Vec3::from(p.unwrap_or((0.0,0.0,0.0)));
-                let r = r.unwrap_or(0.0);
-                
-                prebaked.push(PreBakedModel::Face{
-                    n,
-                    p,
-                    r,
-                    s,
-                    t,
-                    cullable
-                });
-            },
-
-            MeshElement::MeshImport { src, t, cullable } => {
-                let (verts, uvs, normals, indices) = load_mesh_file(src).unwrap();
-
-                let t = {
-                    let index = variables
-                        .iter()
-                        .enumerate()
-                        .find_map(|x| if x.1.0 == t.src {Some(x.0 as i32)} else {None})
-                        .unwrap_or_else(|| panic!("unreqognized variable {}",t.src));
-
-                    (index,t.clip.map(|(x, y, z, w)| IVec4::new(x as i32, y as i32, z as i32, w as i32)))
-                };
-
-                prebaked.push(PreBakedModel::Mesh{
-                    indices,
-                    normals,
-                    uvs,
-                    t,
-                    verts,
-                    cullable
-                });
-            },
-
-            MeshElement::Mesh { verts, uvs, normals, indices, t } => {
-                let t = {
-                    let index = variables
-                        .iter()
-                        .enumerate()
-                        .find_map(|x| if x.1.0 == t.src {Some(x.0 as i32)} else {None})
-                        .unwrap_or_else(|| panic!("unreqognized variable {}",t.src));
-
-                    (index,t.clip.map(|(x, y, z, w)| IVec4::new(x as i32, y as i32, z as i32, w as i32)))
-                };
-
-                let verts: Vec<Vec3> = verts.into_iter().map(|x| x.into()).collect();
-                let uvs: Vec<Vec2> = uvs.into_iter().map(|x| x.into()).collect();
-                let normals: Vec<Vec3> = normals.unwrap().into_iter().map(|x|x.into()).collect();
-                let indices: Vec<u16> = indices.into_iter().flat_map(|x| [x.0,x.1,x.2].into_iter()).collect();
-
-                prebaked.push(PreBakedModel::Mesh{
-                    indices,
-                    normals,
-                    t,
-                    uvs,
-                    verts,
-                    cullable: Cullable::Never
-                });
-            },
-        }
-    }
-
-    file_cache.insert(path, (variables,prebaked, transparent));
-}
\ No newline at end of file
+///
+/// `rotation` is in degrees around the face normal, counter-clockwise when looking at the face.
+/// The uvs stay on their corners, so the texture turns with the quad.
+pub(crate) fn create_quad(normal: Direction, size: Vec2, position: Vec3, rotation: f32) -> ([Vec3;4], [Vec2;4], Vec3){
+    quad::create_quad(normal.into(), size, position, rotation)
+}
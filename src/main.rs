@@ -1,35 +1,30 @@
-#![feature(iterator_try_collect)]
-#![feature(maybe_uninit_uninit_array)]
-#![feature(maybe_uninit_array_assume_init)]
-mod loaders;
-pub mod util;
-pub mod voxel;
-pub mod world;
-
 use bevy::{
     prelude::{
-        default, App, Assets, Camera3dBundle, Commands, EventWriter,
-        Handle, IVec3, MaterialPlugin, Mesh, Query, Res, ResMut, SystemSet, Transform, Vec3, With, Camera, Input, KeyCode, Resource, ImagePlugin, PluginGroup, DirectionalLightBundle, AmbientLight, Color, DirectionalLight, Material, StandardMaterial,
+        default, App, Assets, Camera3dBundle, Commands,
+        Handle, IVec3, MaterialPlugin, Mesh, Query, Res, ResMut, SystemSet, Transform, Vec3, With, Camera, Input, KeyCode, Resource, IntoSystemDescriptor, ImagePlugin, PluginGroup, DirectionalLightBundle, AmbientLight, Color, DirectionalLight, Material, StandardMaterial,
     },
     DefaultPlugins, time::Time,
 };
-use df_rust::clients::remote_fortress_reader::RemoteFortressReader;
-use voxel::{model_storage::{ModelStorage, ModelRegistry}};
-use world::{
-    events::{
-        chunk_builder::{ChunkBuildEvent, handle_loading, VOXEL_MATERIAL},
-        chunk_loading::{ChunkLoadEvent, create_loader},
+use volum2::{
+    AppState,
+    fortress::FortressResource,
+    loaders,
+    voxel::{model_storage::{ModelStorage, ModelRegistry}},
+    world::{
+        events::{
+            building_sync::{BuildingEntities, sync_buildings},
+            chunk_builder::{ChunkBuildEvent, handle_loading, build_dirty_chunks, finish_chunk_meshes, remesh_on_reload, add_liquid_materials, MeshingTasks, VOXEL_MATERIAL},
+            chunk_loading::{ChunkLoadEvent, create_loader},
+            chunk_streaming::{ChunkStreamer, StreamingSettings, stream_chunks},
+            map_polling::{MapPoller, poll_map_changes, POLL_INTERVAL},
+            unit_sync::{UnitEntities, UnitPoller, poll_units, move_units, UNIT_POLL_INTERVAL},
+        },
+        building::BuildingRegistry,
+        unit::CreatureRegistry,
+        World, MaterialRegistry,
     },
-    World, MaterialRegistry,
 };
 
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum AppState {
-    Setup,
-    Running,
-}
-
 fn main() {
     let mut app = App::new();
     app.add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
@@ -38,32 +33,45 @@ fn main() {
             brightness: 0.1,
             color: Color::WHITE
         })
-        .insert_resource(FortressResource(RemoteFortressReader::new(Some("127.0.0.1:5000"))))
+        .insert_resource(FortressResource::from_args())
         .add_state(AppState::Setup)
         .insert_resource(World::new())
+        .insert_resource(StreamingSettings::from_args())
+        .insert_resource(MapPoller::new(POLL_INTERVAL))
+        .init_resource::<MeshingTasks>()
         .init_resource::<MaterialRegistry>()
+        .init_resource::<BuildingRegistry>()
+        .init_resource::<BuildingEntities>()
+        .insert_resource(UnitPoller::new(UNIT_POLL_INTERVAL))
+        .init_resource::<CreatureRegistry>()
+        .init_resource::<UnitEntities>()
         .add_event::<ChunkBuildEvent>()
         .add_event::<ChunkLoadEvent>()
+        .add_startup_system(add_liquid_materials)
         .add_system_set(SystemSet::on_enter(AppState::Running).with_system(startup_system))
         .add_system_set(
             SystemSet::on_update(AppState::Running)
                 //.with_system(rotator)
                 
-                .with_system(create_loader)
+                .with_system(stream_chunks)
+                .with_system(create_loader.after(stream_chunks))
                 .with_system(handle_loading)
+                .with_system(poll_map_changes)
+                .with_system(build_dirty_chunks.after(handle_loading).after(poll_map_changes))
+                .with_system(finish_chunk_meshes)
+                .with_system(remesh_on_reload.before(build_dirty_chunks))
+                .with_system(sync_buildings.after(handle_loading).after(poll_map_changes).after(stream_chunks))
+                .with_system(poll_units)
+                .with_system(move_units.after(poll_units))
                 .with_system(camera_mover)
         );
 
     loaders::add_loading_methods(&mut app).run();
 }
 
-#[derive(Resource)]
-pub struct FortressResource(pub RemoteFortressReader);
-
 fn startup_system(
-    mut client: ResMut<FortressResource>,
+    fortress: Res<FortressResource>,
     mut commands: Commands,
-    mut writer: EventWriter<ChunkLoadEvent>,
 ) {
 
     commands.spawn(Camera3dBundle {
@@ -80,20 +88,15 @@ fn startup_system(
         ..default()
     });
 
-    client.0.reset_map_hashes();
-
-    let info = client.0.get_map_info();
+    let info = {
+        let mut client = fortress.lock();
+        client.reset_map_hashes();
+        client.get_map_info()
+    };
 
-    for x in 0..info.block_size_x() {
-        for y in 0..info.block_size_y() {
-            for z in (0..info.block_size_z()).step_by(16) {
-                writer.send(ChunkLoadEvent {
-                    entity: commands.spawn_empty().id(),
-                    map_pos: IVec3::new(x, y, z),
-                });
-            }
-        }
-    }
+    commands.insert_resource(ChunkStreamer::new(
+        IVec3::new(info.block_size_x(), info.block_size_y(), info.block_size_z())
+    ));
 }
 
 
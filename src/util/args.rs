@@ -0,0 +1,27 @@
+use std::{env, str::FromStr, fmt::Debug};
+
+///
+/// Returns the value following `name` on the command line, e.g. `--address 127.0.0.1:5000`.
+pub fn arg_value(name: &str) -> Option<String> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return Some(args.next().unwrap_or_else(|| panic!("{} requires a value", name)));
+        }
+    }
+    None
+}
+
+///
+/// Whether a flag without a value, e.g. `--list-packs`, is on the command line.
+pub fn has_flag(name: &str) -> bool {
+    env::args().skip(1).any(|x| x == name)
+}
+
+pub fn parsed_arg<T>(name: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    arg_value(name).map(|x| x.parse().unwrap_or_else(|e| panic!("invalid value for {}: {:?}", name, e)))
+}
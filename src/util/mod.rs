@@ -1,7 +1,9 @@
+pub mod args;
 
 pub mod client_pool;
 pub mod display_iter;
 pub mod event_queue;
-pub mod mesh_loader;
 pub mod result_ext;
 pub mod cache;
+#[cfg(test)]
+pub mod test_files;
@@ -0,0 +1,14 @@
+use std::{env, fs, path::{Path, PathBuf}, process};
+
+///
+/// Writes a file for a test, creating the folders leading to it.
+pub fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+///
+/// A path in the temp folder no other test or test run uses, named after the test.
+pub fn temp_path(test: &str) -> PathBuf {
+    env::temp_dir().join(format!("kanelsnurrer_{}_{}", test, process::id()))
+}
@@ -1,9 +1,9 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display};
 
 use bevy::prelude::{Vec3, Vec2, Handle, Image};
 use serde::{Deserialize, Serialize};
 
-use crate::{util::display_iter::DisplayableExt, loaders::model_loader::BakedModel};
+use crate::{util::display_iter::DisplayableExt, loaders::model_loader::{BakedModel, Direction}};
 
 pub mod model_storage;
 
@@ -22,6 +22,10 @@ impl Display for ModelEntry {
 pub struct ModelData{
     pub models: Vec<BakedModel>,
     pub transparent: bool,
+    /// Vertex colours are set to the material colour instead of white.
+    pub tint: bool,
+    /// Whether the side facing each direction hides the neighbour there, sides left out follow the tile shape.
+    pub visibility: BTreeMap<Direction, bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1,6 +1,6 @@
-use std::{fmt::{Display, Debug}, mem::MaybeUninit};
+use std::{collections::BTreeSet, fmt::{Display, Debug}, mem::MaybeUninit, sync::{Arc, Mutex, RwLock}};
 
-use bevy::prelude::{Assets, ResMut, Shader, Resource};
+use bevy::prelude::{warn, Assets, ResMut, Shader, Resource};
 use df_rust::clients::remote_fortress_reader::remote_fortress_reader::TiletypeShape;
 
 use crate::{
@@ -52,15 +52,61 @@ impl<T> RegistryContainers<T>{
 
 }
 
-#[derive(Resource)]
+///
+/// Cloning is cheap, the storages are shared so meshing tasks can hold on to the registry while it's in use.
+#[derive(Resource, Clone)]
 pub struct ModelRegistry{
-    container: RegistryContainers<ModelStorage>
+    container: Arc<RegistryContainers<ModelStorage>>,
+    /// Building models aren't tied to a tile shape, they are looked up by building type and then material.
+    buildings: Arc<ModelStorage>,
+    /// Unit models by creature race.
+    creatures: Arc<ModelStorage>,
 }
 
 impl ModelRegistry{
     pub fn new() -> Self{
         Self{
-            container: RegistryContainers::new(ModelStorage::new)
+            container: Arc::new(RegistryContainers::new(ModelStorage::new)),
+            buildings: Arc::new(ModelStorage::new()),
+            creatures: Arc::new(ModelStorage::new()),
+        }
+    }
+
+    pub fn get_building_storage(&self) -> &ModelStorage{
+        &self.buildings
+    }
+
+    pub fn get_building_storage_mut(&mut self) -> &mut ModelStorage{
+        Arc::get_mut(&mut self.buildings)
+            .expect("model registry can't be modified while it's shared")
+    }
+
+    pub fn get_building_model(&self, id: &Identifier) -> Option<&ModelEntry>{
+        match self.buildings.get_model_and_cache(id){
+            Ok(model) => model,
+            Err(model) => {
+                self.buildings.report_missing(id, "buildings");
+                model
+            },
+        }
+    }
+
+    pub fn get_creature_storage(&self) -> &ModelStorage{
+        &self.creatures
+    }
+
+    pub fn get_creature_storage_mut(&mut self) -> &mut ModelStorage{
+        Arc::get_mut(&mut self.creatures)
+            .expect("model registry can't be modified while it's shared")
+    }
+
+    pub fn get_creature_model(&self, id: &Identifier) -> Option<&ModelEntry>{
+        match self.creatures.get_model_and_cache(id){
+            Ok(model) => model,
+            Err(model) => {
+                self.creatures.report_missing(id, "creatures");
+                model
+            },
         }
     }
 
@@ -70,15 +116,17 @@ impl ModelRegistry{
     }
 
     pub fn get_storage_entry_mut(&mut self, shape: TiletypeShape) -> &mut ModelStorage{
-        self.container.get_mut(shape)
+        Arc::get_mut(&mut self.container)
+            .expect("model registry can't be modified while it's shared")
+            .get_mut(shape)
     }
 
-    pub fn get_model_and_cache(&mut self, id: &Identifier, shape: TiletypeShape) -> Option<&ModelEntry>{
-        match self.container.get_mut(shape).get_model_and_cache(id){
+    pub fn get_model_and_cache(&self, id: &Identifier, shape: TiletypeShape) -> Option<&ModelEntry>{
+        match self.container.get(shape).get_model_and_cache(id){
             Ok(model) => model,
             Err(model) => {
                 if id.last() != Some("STRUCTURAL"){
-                    eprintln!("Missing key in {:?} : {}",shape,id);
+                    self.container.get(shape).report_missing(id, format!("{:?}", shape));
                 }
                 model
             },
@@ -96,7 +144,7 @@ impl ModelRegistry{
             Ok(model) => model,
             Err(model) => {
                 if shape != TiletypeShape::NoShape{
-                    println!("missing model {:?} {:?}",shape, id);
+                    self.container.get(shape).report_missing(id, format!("{:?}", shape));
                 }
                 model
             },
@@ -106,13 +154,18 @@ impl ModelRegistry{
 }
 pub struct ModelStorage {
     models: Vec<ModelEntry>,
+    /// Identifier each model was added under, empty for the default model.
+    names: Vec<Identifier>,
 
-    identifiers: Cache<Identifier,u32>,
+    /// Lookups fill in missing identifiers from their parents, so the cache is written to through a shared reference.
+    identifiers: RwLock<Cache<Identifier,u32>>,
+    /// Identifiers already reported as missing, lookups happen every rebuild so each is only reported once.
+    missing: Mutex<BTreeSet<String>>,
 }
 
 impl Debug for ModelStorage{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("ModelStorage").field(&self.identifiers).finish()
+        f.debug_tuple("ModelStorage").field(&*self.identifiers.read().unwrap()).finish()
     }
 }
 
@@ -120,23 +173,34 @@ impl ModelStorage {
     pub fn new() -> Self {
         Self {
             models: Vec::new(),
-            identifiers: Cache::new_with_default(0),
+            names: Vec::new(),
+            identifiers: RwLock::new(Cache::new_with_default(0)),
+            missing: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    fn report_missing(&self, id: &Identifier, storage: impl Display) {
+        if self.missing.lock().unwrap().insert(id.to_string()){
+            warn!("missing key in {} : {}", storage, id);
         }
     }
 
     pub fn get_model_id(&self, id: &Identifier) -> Result<u32,u32> {
-        self.identifiers.get_recursive(id).ok_or(0).cloned()
+        self.identifiers.read().unwrap().get_recursive(id).ok_or(0).cloned()
     }
 
     pub fn get_model(&self, id: &Identifier) -> Result<Option<&ModelEntry>,Option<&ModelEntry>>{
         self.get_model_id(id).map_either(|index| self.models.get((index-1) as usize))
     }
 
-    pub fn get_model_id_and_cache(&mut self, id: &Identifier) -> Result<Option<u32>,Option<u32>>{
-        self.identifiers.get_or_initialize_with_parent(id).map_either(|x| x.cloned())
+    pub fn get_model_id_and_cache(&self, id: &Identifier) -> Result<Option<u32>,Option<u32>>{
+        if let Some(cached) = self.identifiers.read().unwrap().get(id){
+            return Ok(Some(*cached));
+        }
+        self.identifiers.write().unwrap().get_or_initialize_with_parent(id).map_either(|x| x.cloned())
     }
 
-    pub fn get_model_and_cache(&mut self, id: &Identifier) -> Result<Option<&ModelEntry>,Option<&ModelEntry>>{
+    pub fn get_model_and_cache(&self, id: &Identifier) -> Result<Option<&ModelEntry>,Option<&ModelEntry>>{
         self.get_model_id_and_cache(id).map_either(|x|{
             x.map(|y| self.models.get((y-1) as usize)).flatten()
         })
@@ -144,19 +208,27 @@ impl ModelStorage {
 
     pub fn add_model(&mut self, model: ModelEntry, identifier: Identifier) {
         self.models.push(model);
+        self.names.push(identifier.clone());
         let id = self.models.len() as u32;
 
+        let identifiers = self.identifiers.get_mut().unwrap();
         if identifier.is_empty(){
-            self.identifiers.set_default(id);
+            identifiers.set_default(id);
         }
         else{
-            self.identifiers
+            identifiers
             .set(identifier, id);
         }
     }
 
+    ///
+    /// Models in the order they were added, adding them again in this order gives the same storage.
+    pub fn iter(&self) -> impl Iterator<Item = (&Identifier, &ModelEntry)>{
+        self.names.iter().zip(self.models.iter())
+    }
+
     pub fn print_tree(&self) {
-        println!("{:#?}",self.identifiers);
+        println!("{:#?}",self.identifiers.read().unwrap());
     }
 
     pub fn generate_shader_assets(&self, mut shaders: ResMut<Assets<Shader>>) {
@@ -0,0 +1,126 @@
+use std::{collections::BTreeMap, f32::consts::{FRAC_PI_2, PI}};
+
+use bevy::prelude::{FromWorld, IVec3, Quat, Resource, Transform, Vec3};
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{BuildingDirection, BuildingInstance};
+
+use crate::fortress::FortressResource;
+
+use super::{tile::material_identifier::Identifier, Matpair, MaterialRegistry};
+
+///
+/// (type, subtype, custom) as DFHack sends them, -1 when unused.
+pub type BuildingKind = (i32, i32, i32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Building {
+    pub index: i32,
+    /// DF tile coordinates, max is inclusive.
+    pub min: IVec3,
+    pub max: IVec3,
+    pub kind: BuildingKind,
+    pub material: Matpair,
+    pub direction: BuildingDirection,
+}
+
+impl From<&BuildingInstance> for Building {
+    fn from(x: &BuildingInstance) -> Self {
+        let kind = x.building_type
+            .as_ref()
+            .map_or((-1, -1, -1), |t| (t.building_type, t.building_subtype, t.building_custom));
+
+        Self {
+            index: x.index,
+            min: IVec3::new(x.pos_x_min(), x.pos_y_min(), x.pos_z_min()),
+            max: IVec3::new(x.pos_x_max(), x.pos_y_max(), x.pos_z_max()),
+            kind,
+            material: x.material.clone().map(Matpair::from).unwrap_or_default(),
+            direction: x.direction(),
+        }
+    }
+}
+
+impl Building {
+    ///
+    /// Every block a building overlaps lists it, only the block holding its min corner owns it.
+    pub fn is_owned_by(&self, map_x: i32, map_y: i32, map_z: i32) -> bool {
+        self.min.x.div_euclid(16) == map_x.div_euclid(16)
+            && self.min.y.div_euclid(16) == map_y.div_euclid(16)
+            && self.min.z == map_z
+    }
+
+    pub fn chunk_key(&self) -> (i32, i32, i32) {
+        (self.min.x.div_euclid(16), self.min.z.div_euclid(16), self.min.y.div_euclid(16))
+    }
+
+    ///
+    /// Models are authored for a single tile facing Forward (south in DF),
+    /// they get stretched over the whole footprint and turned towards the building direction.
+    pub fn transform(&self) -> Transform {
+        let center = (self.min + self.max).as_vec3() / 2.0;
+        let size = (self.max - self.min + IVec3::ONE).as_vec3();
+
+        let (rotation, scale) = match self.direction {
+            BuildingDirection::North => (PI, Vec3::new(size.x, 1.0, size.y)),
+            BuildingDirection::East => (FRAC_PI_2, Vec3::new(size.y, 1.0, size.x)),
+            BuildingDirection::West => (-FRAC_PI_2, Vec3::new(size.y, 1.0, size.x)),
+            _ => (0.0, Vec3::new(size.x, 1.0, size.y)),
+        };
+
+        //Flipping coordinates
+        Transform {
+            translation: Vec3::new(center.x, self.min.z as f32, center.y),
+            rotation: Quat::from_rotation_y(rotation),
+            scale,
+        }
+    }
+}
+
+///
+/// Maps DFHack building types to identifiers, "Workshop/Masons" becomes WORKSHOP::MASONS.
+#[derive(Resource)]
+pub struct BuildingRegistry {
+    ids: BTreeMap<BuildingKind, Identifier>,
+}
+
+impl BuildingRegistry {
+    ///
+    /// Falls back to the definition without the custom type, then without the subtype.
+    pub fn get_identifier(&self, kind: BuildingKind) -> Option<&Identifier> {
+        let (type_, subtype, custom) = kind;
+        self.ids.get(&kind)
+            .or_else(|| self.ids.get(&(type_, subtype, -1)))
+            .or_else(|| self.ids.get(&(type_, -1, -1)))
+    }
+
+    ///
+    /// Building identifier followed by the material identifier, e.g. DOOR::INORGANIC::GRANITE,
+    /// so the model tree can be split by material the same way tiles are.
+    pub fn get_model_identifier(&self, building: &Building, materials: &MaterialRegistry) -> Option<Identifier> {
+        let id = self.get_identifier(building.kind)?;
+        let material = materials.matdefs.get(&building.material).and_then(|x| x.id.as_ref());
+
+        let elements = match material {
+            Some(material) => id.elements().iter().chain(material.elements()).cloned().collect(),
+            None => id.elements().to_vec(),
+        };
+        Some(Identifier::from(elements))
+    }
+}
+
+impl FromWorld for BuildingRegistry {
+    fn from_world(world: &mut bevy::prelude::World) -> Self {
+        let mut client = world.resource::<FortressResource>().lock();
+        let ids = client.get_building_def_list().building_list.into_iter().filter_map(
+            |x|{
+                let t = x.building_type;
+                let id = String::from_utf8_lossy(&x.id?).replace('/', ":");
+                Some((
+                    (t.building_type, t.building_subtype, t.building_custom),
+                    Identifier::from(id)
+                ))
+            }
+        ).collect();
+
+        Self { ids }
+    }
+}
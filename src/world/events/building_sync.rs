@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::{default, Assets, Commands, Component, DespawnRecursiveExt, Entity, EventReader, Handle, Mesh, PbrBundle, Res, ResMut, Resource};
+
+use crate::{
+    loaders::model_loader::ModelsBaked,
+    voxel::model_storage::ModelRegistry,
+    world::{
+        building::BuildingRegistry, meshing::{build_model_mesh, MeshLayer}, tile::material_identifier::Identifier, MaterialRegistry, World,
+    },
+};
+
+#[derive(Component)]
+pub struct BuildingComponent(pub i32);
+
+///
+/// Spawned building entities by building index, meshes are shared by every building using the same identifier.
+#[derive(Resource, Default)]
+pub struct BuildingEntities {
+    entities: BTreeMap<i32, Entity>,
+    meshes: BTreeMap<Identifier, (Handle<Mesh>, MeshLayer)>,
+}
+
+pub fn sync_buildings(
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    buildings: Res<BuildingRegistry>,
+    materials: Res<MaterialRegistry>,
+    models: Res<ModelRegistry>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut spawned: ResMut<BuildingEntities>,
+    mut baked: EventReader<ModelsBaked>,
+){
+    //Cached meshes were built from the old models
+    if baked.iter().count() > 0{
+        spawned.meshes.clear();
+        world.mark_all_buildings_changed();
+    }
+
+    for index in world.take_changed_buildings(){
+        if let Some(entity) = spawned.entities.remove(&index){
+            commands.entity(entity).despawn_recursive();
+        }
+
+        let Some(building) = world.get_building(index) else{
+            continue;
+        };
+
+        let Some(id) = buildings.get_model_identifier(building, &materials) else{
+            continue;
+        };
+
+        let (mesh, layer) = match spawned.meshes.get(&id).cloned(){
+            Some(cached) => cached,
+            None => {
+                //Stockpiles, zones and constructions have empty models, they are drawn by the tiles
+                let Some(model) = models.get_building_model(&id).filter(|x| !x.0.models.is_empty()) else{
+                    continue;
+                };
+                let layer = MeshLayer::for_model(model);
+                let mesh = meshes.add(build_model_mesh(model, materials.get_color(&building.material)));
+                spawned.meshes.insert(id, (mesh.clone(), layer));
+                (mesh, layer)
+            }
+        };
+
+        let entity = commands.spawn((
+            PbrBundle{
+                mesh,
+                material: layer.material(),
+                transform: building.transform(),
+                ..default()
+            },
+            BuildingComponent(index)
+        )).id();
+
+        spawned.entities.insert(index, entity);
+    }
+}
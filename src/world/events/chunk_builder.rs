@@ -1,17 +1,21 @@
+use std::collections::BTreeMap;
+
 use bevy::{
     prelude::{
-        default, Assets, Commands, Entity, Handle, IVec3,
-        MaterialMeshBundle, Mesh, Query, Res, ResMut, Transform, StandardMaterial, PbrBundle, HandleUntyped, Material,
-    }, reflect::TypeUuid,
+        default, Assets, Commands, Entity, IVec3,
+        Mesh, Query, Res, ResMut, Resource, Transform, StandardMaterial, PbrBundle, HandleUntyped,
+        BuildChildren, Color, AlphaMode, EventReader, warn,
+    }, reflect::TypeUuid, tasks::{AsyncComputeTaskPool, Task},
 };
-use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{MapBlock, TiletypeShape};
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::MapBlock;
 use futures_lite::future;
 
 use crate::{
-    voxel::{model_storage::{ModelStorage, ModelRegistry}},
+    loaders::model_loader::ModelsBaked,
+    voxel::model_storage::ModelRegistry,
     world::{
-        tile::Tile, World, meshing::build_mesh, MaterialRegistry,
-    }, loaders::model_loader::ModelLoadingData,
+        World, BlockContents, meshing::{build_mesh, snapshot::ChunkSnapshot, MeshBuilder, MeshLayer, LAYER_COUNT}, MaterialRegistry,
+    },
 };
 
 use super::chunk_loading::LoadData;
@@ -85,86 +89,174 @@ pub struct ChunkBuildEvent {
 pub const VOXEL_MATERIAL: HandleUntyped = 
     HandleUntyped::weak_from_u64(StandardMaterial::TYPE_UUID, 12012309628019059972);
 
+///
+/// Same atlas as [`VOXEL_MATERIAL`], blended so glass, ice and leaves are sorted and see-through.
+pub const TRANSPARENT_MATERIAL: HandleUntyped =
+    HandleUntyped::weak_from_u64(StandardMaterial::TYPE_UUID, 12012309628019059975);
+
+pub const WATER_MATERIAL: HandleUntyped =
+    HandleUntyped::weak_from_u64(StandardMaterial::TYPE_UUID, 12012309628019059973);
+
+pub const MAGMA_MATERIAL: HandleUntyped =
+    HandleUntyped::weak_from_u64(StandardMaterial::TYPE_UUID, 12012309628019059974);
+
+pub fn add_liquid_materials(
+    mut materials: ResMut<Assets<StandardMaterial>>
+){
+    materials.get_or_insert_with(WATER_MATERIAL.typed::<StandardMaterial>(), ||StandardMaterial{
+        base_color: Color::rgba(0.15, 0.35, 0.8, 0.6),
+        perceptual_roughness: 0.1,
+        reflectance: 0.5,
+        alpha_mode: AlphaMode::Blend,
+        ..default()
+    });
+
+    materials.get_or_insert_with(MAGMA_MATERIAL.typed::<StandardMaterial>(), ||StandardMaterial{
+        base_color: Color::rgba(1.0, 0.3, 0.05, 0.9),
+        emissive: Color::rgb(1.0, 0.35, 0.0),
+        alpha_mode: AlphaMode::Blend,
+        ..default()
+    });
+}
+
 pub fn handle_loading(
     mut commands: Commands,
     mut query: Query<(Entity, &mut LoadData)>,
-    mesh_query: Query<(Entity, &mut Handle<Mesh>)>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut world: ResMut<World>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    material_registry: Res<MaterialRegistry>,
-    mut model_storage: ResMut<ModelRegistry>,
-    model_data: Res<ModelLoadingData>
 ){
-
-    
-
     for (entity, mut data) in &mut query{
         if let Some(event) = future::block_on(future::poll_once(&mut data.0)){
 
             //println!("received data for {}",event.position);
 
             let pos = event.position;
-            let block = &event.block;
-
-            let df_z_coord = pos.z;
-
             let pos = IVec3::new(pos.x, pos.z / 16, pos.y);
+            let key = (pos.x, pos.y, pos.z);
 
+            //Blocks DFHack sent before the chunk was unloaded aren't sent again, only their changes
+            let contents = if world.restore_chunk(key) { BlockContents::Changes } else { BlockContents::Full };
+            for block in &event.block{
+                let block_key = World::block_key(block);
+                if block_key != key{
+                    warn!("skipping block {:?} in the reply for chunk {:?}, it belongs to chunk {:?}", (block.map_x, block.map_y, block.map_z), key, block_key);
+                    continue;
+                }
+                world.apply_block(block, contents);
+            }
 
+            let chunk = world.chunk_mut(key);
+            chunk.id = entity;
 
-            {
-                let chunk = world.chunk_mut((pos.x, pos.y, pos.z));
-                
-                for block in block{
-                    let y = block.map_z - df_z_coord;
-                    assert!(y >= 0);
-                    for x in 0..16 {
-                        for z in 0..16 {
-                            let id = (x + z * 16) as usize;
-            
-                            let tile = Tile {
-                                tile_id: block.tiles[id],
-                                mat_pair: block.materials[id].clone().into(),
-                                base_mat: block.base_materials[id].clone().into(),
-                                hidden: block.hidden[id],
-                            };
-            
-                            chunk.set_tile(x, y as i32, z, tile)
-                        }
+            if chunk.meshes.is_none(){
+                let handles = MeshLayer::ALL.map(|_| meshes.add(MeshBuilder::default().into_mesh()));
+
+                //The solid layer lives on the chunk entity, the others on children sharing its transform
+                commands.entity(entity)
+                .insert(PbrBundle{
+                    mesh: handles[MeshLayer::Solid.index()].clone(),
+                    material: MeshLayer::Solid.material(),
+                    transform: Transform::from_xyz((pos.x * 16) as f32, (pos.y * 16) as f32, (pos.z * 16) as f32),
+                    ..default()
+                })
+                .with_children(|parent|{
+                    for layer in MeshLayer::ALL.into_iter().filter(|x| *x != MeshLayer::Solid){
+                        parent.spawn(PbrBundle{
+                            mesh: handles[layer.index()].clone(),
+                            material: layer.material(),
+                            ..default()
+                        });
                     }
-                }            
+                });
+                chunk.meshes = Some(handles);
             }
-            
-            let mesh = match mesh_query.get(entity){
-                Ok((_, mesh)) => {
-                    meshes.get_mut(mesh).unwrap()
-                },
-                Err(_) => {
-                    let handle = meshes.add(Mesh::new(bevy::render::render_resource::PrimitiveTopology::TriangleList));
-                    commands.entity(entity)
-                    .insert(PbrBundle{
-                        mesh: handle.clone(),
-                        material: VOXEL_MATERIAL.typed().clone(),
-                        transform: Transform::from_xyz((pos.x * 16) as f32, (pos.y * 16) as f32, (pos.z * 16) as f32),
-                        ..default()
-                    });
-                    
-                    meshes.get_mut(&handle).unwrap()      
-                }
-            };
-
-            let chunk = world.chunk((pos.x,pos.y,pos.z));
 
-            build_mesh(
-                mesh,
-                &chunk,
-                &material_registry,
-                &mut model_storage
-            );
+            world.mark_dirty_with_neighbours(key);
             commands.entity(entity).remove::<LoadData>();
             //println!("Removed LoadData for {}",pos);
             //model_storage.get_storage_entry(TiletypeShape::Wall).print_tree();
         }
     }
-}
\ No newline at end of file
+}
+
+///
+/// Meshes being built on the task pool, by chunk key.
+#[derive(Resource, Default)]
+pub struct MeshingTasks(BTreeMap<(i32, i32, i32), Task<[Mesh; LAYER_COUNT]>>);
+
+///
+/// Starts rebuilding the mesh of every chunk whose tiles changed since the last frame.
+/// A chunk that is dirtied again while its mesh is being built has its old task dropped.
+pub fn build_dirty_chunks(
+    mut world: ResMut<World>,
+    mut tasks: ResMut<MeshingTasks>,
+    material_registry: Res<MaterialRegistry>,
+    model_storage: Res<ModelRegistry>,
+){
+    let pool = AsyncComputeTaskPool::get();
+
+    for key in world.take_dirty(){
+        let Some(chunk) = world.get_chunk(key) else{
+            continue;
+        };
+
+        if chunk.meshes.is_none(){
+            continue;
+        }
+
+        let snapshot = ChunkSnapshot::new(&world, key);
+        let registry = material_registry.clone();
+        let models = model_storage.clone();
+
+        let task = pool.spawn(async move{
+            build_mesh(
+                &snapshot,
+                &registry,
+                &models
+            )
+        });
+        tasks.0.insert(key, task);
+    }
+}
+
+///
+/// Rebuilds every loaded chunk once the models have been baked again.
+pub fn remesh_on_reload(
+    mut baked: EventReader<ModelsBaked>,
+    mut world: ResMut<World>,
+){
+    if baked.iter().count() > 0{
+        world.mark_all_dirty();
+    }
+}
+
+///
+/// Swaps finished meshes into their chunk's mesh asset.
+pub fn finish_chunk_meshes(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut tasks: ResMut<MeshingTasks>,
+    world: Res<World>,
+){
+    let mut finished = Vec::new();
+
+    for (key, task) in tasks.0.iter_mut(){
+        if let Some(mesh) = future::block_on(future::poll_once(task)){
+            finished.push((*key, mesh));
+        }
+    }
+
+    for (key, layers) in finished{
+        tasks.0.remove(&key);
+
+        //The chunk may have been unloaded while it was being meshed
+        let Some(handles) = world.get_chunk(key).and_then(|x| x.meshes.as_ref()) else{
+            continue;
+        };
+
+        for (handle, mesh) in handles.iter().zip(layers){
+            if let Some(target) = meshes.get_mut(handle){
+                *target = mesh;
+            }
+        }
+    }
+}
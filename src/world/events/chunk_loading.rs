@@ -1,41 +1,34 @@
-use std::{os::windows::thread, thread::sleep, time::Duration, sync::{RwLock, Mutex}};
-
-use bevy::{prelude::{EventReader, EventWriter, Res, ResMut, Component, Entity, Query, Commands}, tasks::{Task, AsyncComputeTaskPool}, render::once_cell::sync::Lazy};
-use df_rust::clients::remote_fortress_reader::{
-    remote_fortress_reader::BlockRequest, RemoteFortressReader,
-};
+use bevy::{prelude::{EventReader, Res, Component, Entity, Commands}, tasks::{Task, AsyncComputeTaskPool}};
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::BlockRequest;
 
 use bevy::prelude::IVec3;
 
-use crate::util::{client_pool::ResourcePool, event_queue::EventQueue};
+use crate::fortress::FortressResource;
 
 use super::chunk_builder::ChunkBuildEvent;
 pub struct ChunkLoadEvent {
     pub entity: Entity,
     pub map_pos: IVec3,
+    /// Reset DFHack's map hashes first, the chunk's blocks were sent before but its tiles have been dropped.
+    pub reset_hashes: bool,
 }
 
-
-static REMOTE_CLIENT: Lazy<Mutex<RemoteFortressReader>> = Lazy::new(||{
-    Mutex::new(
-        RemoteFortressReader::new(Some("127.0.0.1:5000"))
-    )
-});
-
 #[derive(Component)]
 pub struct LoadData(pub Task<ChunkBuildEvent>);
 
 pub fn create_loader(
     mut reader: EventReader<ChunkLoadEvent>,
-    mut query: Query<Entity>,
+    fortress: Res<FortressResource>,
     mut commands: Commands,
 ){
     let tp = AsyncComputeTaskPool::get();
 
     for event in reader.iter(){
-    
+
         let pos = event.map_pos;
-        
+        let reset_hashes = event.reset_hashes;
+        let fortress = fortress.clone();
+
         let task = tp.spawn(async move{
 
             let request = BlockRequest {
@@ -48,10 +41,11 @@ pub fn create_loader(
                 max_z: Some(pos.z + 16),
             };
 
-            let response = {
-                let mut client = REMOTE_CLIENT.lock().unwrap();
-                client.get_block_list(request)
-            };
+            let mut client = fortress.lock();
+            if reset_hashes{
+                client.reset_map_hashes();
+            }
+            let response = client.get_block_list(request);
 
             ChunkBuildEvent{
                 position: pos,
@@ -62,4 +56,4 @@ pub fn create_loader(
         .insert(LoadData(task));
     }
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::{Camera, Commands, DespawnRecursiveExt, Entity, EventWriter, IVec3, Query, Res, ResMut, Resource, Transform, Vec3, With};
+
+use crate::{util::args::parsed_arg, world::World};
+
+use super::chunk_loading::{ChunkLoadEvent, LoadData};
+
+///
+/// Distances are in tiles, measured from the camera to the center of a chunk.
+#[derive(Resource)]
+pub struct StreamingSettings {
+    pub load_radius: f32,
+    /// Kept larger than `load_radius` so chunks on the edge aren't reloaded every time the camera turns around.
+    pub unload_radius: f32,
+    /// Maximum number of block requests waiting on DFHack at once.
+    pub max_pending: usize,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            load_radius: 96.0,
+            unload_radius: 128.0,
+            max_pending: 8,
+        }
+    }
+}
+
+impl StreamingSettings {
+    ///
+    /// `--view-distance <tiles>` overrides the load radius, the unload radius follows it.
+    pub fn from_args() -> Self {
+        let mut settings = Self::default();
+        if let Some(distance) = parsed_arg::<f32>("--view-distance") {
+            settings.unload_radius = distance + (settings.unload_radius - settings.load_radius);
+            settings.load_radius = distance;
+        }
+        settings
+    }
+}
+
+///
+/// Keeps track of which chunks have been requested.
+/// Keys are world chunk keys, (block x, z level / 16, block y).
+#[derive(Resource)]
+pub struct ChunkStreamer {
+    /// Map size in chunks, using the same flipped axes as the keys.
+    size: IVec3,
+    requested: BTreeMap<(i32, i32, i32), Entity>,
+}
+
+impl ChunkStreamer {
+    pub fn new(block_size: IVec3) -> Self {
+        Self {
+            size: IVec3::new(block_size.x, (block_size.z + 15) / 16, block_size.y),
+            requested: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_streamed(&self, key: (i32, i32, i32)) -> bool {
+        self.requested.contains_key(&key)
+    }
+
+    ///
+    /// Smallest box of DFHack block coordinates covering every requested chunk, max exclusive.
+    pub fn block_bounds(&self) -> Option<(IVec3, IVec3)> {
+        let mut keys = self.requested.keys();
+        let first = keys.next()?;
+        let mut min = IVec3::new(first.0, first.2, first.1 * 16);
+        let mut max = min;
+        for (x, y, z) in keys {
+            let pos = IVec3::new(*x, *z, y * 16);
+            min = min.min(pos);
+            max = max.max(pos);
+        }
+        Some((min, max + IVec3::new(1, 1, 16)))
+    }
+}
+
+fn chunk_center(key: (i32, i32, i32)) -> Vec3 {
+    IVec3::new(key.0, key.1, key.2).as_vec3() * 16.0 + Vec3::splat(8.0)
+}
+
+pub fn stream_chunks(
+    mut commands: Commands,
+    streamer: Option<ResMut<ChunkStreamer>>,
+    settings: Res<StreamingSettings>,
+    mut world: ResMut<World>,
+    camera: Query<&Transform, With<Camera>>,
+    loading: Query<(), With<LoadData>>,
+    mut writer: EventWriter<ChunkLoadEvent>,
+){
+    let Some(mut streamer) = streamer else{
+        return;
+    };
+
+    let Some(camera) = camera.iter().next() else{
+        return;
+    };
+    let eye = camera.translation;
+
+    let far = streamer.requested
+        .iter()
+        .filter(|(key, _)| chunk_center(**key).distance(eye) > settings.unload_radius)
+        .map(|(key, entity)| (*key, *entity))
+        .collect::<Vec<_>>();
+
+    for (key, entity) in far{
+        commands.entity(entity).despawn_recursive();
+        streamer.requested.remove(&key);
+        world.remove_chunk(key);
+    }
+
+    let budget = settings.max_pending.saturating_sub(loading.iter().count());
+    if budget == 0{
+        return;
+    }
+
+    let center = (eye / 16.0).floor().as_ivec3();
+    let reach = (settings.load_radius / 16.0).ceil() as i32 + 1;
+    let min = (center - IVec3::splat(reach)).max(IVec3::ZERO);
+    let max = (center + IVec3::splat(reach)).min(streamer.size - IVec3::ONE);
+
+    let mut wanted = Vec::new();
+    for x in min.x..=max.x{
+        for y in min.y..=max.y{
+            for z in min.z..=max.z{
+                let key = (x, y, z);
+                if streamer.requested.contains_key(&key){
+                    continue;
+                }
+
+                let distance = chunk_center(key).distance(eye);
+                if distance <= settings.load_radius{
+                    wanted.push((distance, key));
+                }
+            }
+        }
+    }
+
+    wanted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    for (_, key) in wanted.into_iter().take(budget){
+        let entity = commands.spawn_empty().id();
+        streamer.requested.insert(key, entity);
+        writer.send(ChunkLoadEvent {
+            entity,
+            map_pos: IVec3::new(key.0, key.2, key.1 * 16),
+            reset_hashes: world.needs_hash_reset(key),
+        });
+    }
+}
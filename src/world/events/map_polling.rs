@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use bevy::{prelude::{Res, ResMut, Resource, Query, With}, tasks::{Task, AsyncComputeTaskPool}, time::{Time, Timer, TimerMode}};
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{BlockRequest, MapBlock};
+use futures_lite::future;
+
+use crate::{fortress::FortressResource, world::{BlockContents, World}};
+
+use super::{chunk_loading::LoadData, chunk_streaming::ChunkStreamer};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+///
+/// Periodically asks DFHack for every block around the streamed chunks.
+/// Since DFHack only returns blocks whose hash changed since they were last sent,
+/// each response holds exactly the blocks that were dug, built or flooded in the meantime.
+#[derive(Resource)]
+pub struct MapPoller {
+    timer: Timer,
+    task: Option<Task<Vec<MapBlock>>>,
+}
+
+impl MapPoller {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            timer: Timer::new(interval, TimerMode::Repeating),
+            task: None,
+        }
+    }
+}
+
+pub fn poll_map_changes(
+    time: Res<Time>,
+    fortress: Res<FortressResource>,
+    mut poller: ResMut<MapPoller>,
+    streamer: Option<Res<ChunkStreamer>>,
+    mut world: ResMut<World>,
+    loading: Query<(), With<LoadData>>,
+){
+    let Some(streamer) = streamer else{
+        return;
+    };
+
+    if let Some(task) = &mut poller.task{
+        if let Some(blocks) = future::block_on(future::poll_once(task)){
+            for block in &blocks{
+                //Blocks outside the streamed area are still kept, DFHack has hashed them and won't send them again
+                let key = world.apply_block(block, BlockContents::Changes);
+                if streamer.is_streamed(key){
+                    world.mark_dirty_with_neighbours(key);
+                }
+            }
+            poller.task = None;
+        }
+        return;
+    }
+
+    //Blocks that are still being loaded haven't been hashed yet, polling now would request them a second time
+    if !loading.is_empty(){
+        return;
+    }
+
+    if !poller.timer.tick(time.delta()).just_finished(){
+        return;
+    }
+
+    let Some((min, max)) = streamer.block_bounds() else{
+        return;
+    };
+    let fortress = fortress.clone();
+
+    poller.task = Some(AsyncComputeTaskPool::get().spawn(async move{
+        let request = BlockRequest {
+            blocks_needed: Some(4096),
+            min_x: Some(min.x),
+            max_x: Some(max.x),
+            min_y: Some(min.y),
+            max_y: Some(max.y),
+            min_z: Some(min.z),
+            max_z: Some(max.z),
+        };
+
+        let mut client = fortress.lock();
+        client.get_block_list(request).map_blocks
+    }));
+}
@@ -1,2 +1,6 @@
+pub mod building_sync;
 pub mod chunk_builder;
 pub mod chunk_loading;
+pub mod chunk_streaming;
+pub mod map_polling;
+pub mod unit_sync;
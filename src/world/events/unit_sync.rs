@@ -0,0 +1,272 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::{prelude::{default, Color, Assets, Commands, Component, DespawnRecursiveExt, Entity, EventReader, Handle, Mesh, PbrBundle, Quat, Query, Res, ResMut, Resource, Transform, Vec3}, tasks::{AsyncComputeTaskPool, Task}, time::{Time, Timer, TimerMode}};
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::UnitList;
+use futures_lite::future;
+
+use crate::{
+    fortress::FortressResource,
+    loaders::model_loader::ModelsBaked,
+    voxel::model_storage::ModelRegistry,
+    world::{
+        meshing::{build_model_mesh, MeshLayer}, tile::material_identifier::Identifier, unit::{CreatureRegistry, Unit},
+    },
+};
+
+pub const UNIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Moves further than this between two updates are teleports (or cage/minecart rides) and aren't interpolated.
+const MAX_STEP: f32 = 3.0;
+
+#[derive(Resource)]
+pub struct UnitPoller {
+    timer: Timer,
+    task: Option<Task<UnitList>>,
+}
+
+impl UnitPoller {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            timer: Timer::new(interval, TimerMode::Repeating),
+            task: None,
+        }
+    }
+}
+
+///
+/// Units walk from `from` to `to` over one poll interval.
+#[derive(Component)]
+pub struct UnitComponent {
+    pub id: i32,
+    from: Vec3,
+    to: Vec3,
+    elapsed: f32,
+}
+
+///
+/// Spawned unit entities by unit id, meshes are shared by every unit of the same race.
+#[derive(Resource, Default)]
+pub struct UnitEntities {
+    entities: BTreeMap<i32, Entity>,
+    meshes: BTreeMap<Identifier, (Handle<Mesh>, MeshLayer)>,
+}
+
+///
+/// What a unit list changes about the spawned units.
+#[derive(Debug, Default, PartialEq)]
+struct UnitChanges {
+    /// Units that aren't spawned yet.
+    appeared: Vec<Unit>,
+    /// Spawned units, at their latest position.
+    moved: Vec<Unit>,
+    /// Ids of spawned units that died, left the map or are no longer listed.
+    gone: Vec<i32>,
+}
+
+impl UnitChanges {
+    fn new(list: &UnitList, spawned: &BTreeMap<i32, Entity>) -> Self {
+        let units = list.creature_list
+            .iter()
+            .filter_map(Unit::from_definition)
+            .map(|x| (x.id, x))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut changes = Self {
+            gone: spawned.keys().filter(|id| !units.contains_key(id)).cloned().collect(),
+            ..default()
+        };
+        for (id, unit) in units {
+            if spawned.contains_key(&id) {
+                changes.moved.push(unit);
+            }
+            else {
+                changes.appeared.push(unit);
+            }
+        }
+        changes
+    }
+}
+
+pub fn poll_units(
+    mut commands: Commands,
+    time: Res<Time>,
+    fortress: Res<FortressResource>,
+    mut poller: ResMut<UnitPoller>,
+    creatures: Res<CreatureRegistry>,
+    models: Res<ModelRegistry>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut spawned: ResMut<UnitEntities>,
+    mut query: Query<(&mut UnitComponent, &mut Transform)>,
+    mut baked: EventReader<ModelsBaked>,
+){
+    //Units are respawned with the new models on the next poll
+    if baked.iter().count() > 0{
+        for (_, entity) in std::mem::take(&mut spawned.entities){
+            commands.entity(entity).despawn_recursive();
+        }
+        spawned.meshes.clear();
+    }
+
+    if let Some(task) = &mut poller.task{
+        let Some(list) = future::block_on(future::poll_once(task)) else{
+            return;
+        };
+        poller.task = None;
+
+        let changes = UnitChanges::new(&list, &spawned.entities);
+
+        for id in changes.gone{
+            if let Some(entity) = spawned.entities.remove(&id){
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+
+        for unit in changes.moved{
+            let target = unit.translation();
+            if let Some(entity) = spawned.entities.get(&unit.id){
+                let Ok((mut component, mut transform)) = query.get_mut(*entity) else{
+                    continue;
+                };
+
+                let step = target - transform.translation;
+                component.from = if step.length() > MAX_STEP { target } else { transform.translation };
+                component.to = target;
+                component.elapsed = 0.0;
+
+                if step.x != 0.0 || step.z != 0.0{
+                    transform.rotation = Quat::from_rotation_y(step.x.atan2(step.z));
+                }
+            }
+        }
+
+        for unit in changes.appeared{
+            let target = unit.translation();
+
+            let model_id = creatures
+                .get_identifier(unit.race)
+                .cloned()
+                .unwrap_or_else(|| Identifier::from("UNKNOWN".to_owned()));
+
+            let (mesh, layer) = match spawned.meshes.get(&model_id).cloned(){
+                Some(cached) => cached,
+                None => {
+                    let Some(model) = models.get_creature_model(&model_id) else{
+                        continue;
+                    };
+                    let layer = MeshLayer::for_model(model);
+                    let mesh = meshes.add(build_model_mesh(model, Color::WHITE));
+                    spawned.meshes.insert(model_id, (mesh.clone(), layer));
+                    (mesh, layer)
+                }
+            };
+
+            let entity = commands.spawn((
+                PbrBundle{
+                    mesh,
+                    material: layer.material(),
+                    transform: Transform::from_translation(target),
+                    ..default()
+                },
+                UnitComponent{
+                    id: unit.id,
+                    from: target,
+                    to: target,
+                    elapsed: 0.0,
+                }
+            )).id();
+
+            spawned.entities.insert(unit.id, entity);
+        }
+        return;
+    }
+
+    if !poller.timer.tick(time.delta()).just_finished(){
+        return;
+    }
+
+    let fortress = fortress.clone();
+    poller.task = Some(AsyncComputeTaskPool::get().spawn(async move{
+        let mut client = fortress.lock();
+        client.get_unit_list()
+    }));
+}
+
+pub fn move_units(
+    time: Res<Time>,
+    mut query: Query<(&mut UnitComponent, &mut Transform)>,
+){
+    for (mut unit, mut transform) in query.iter_mut(){
+        unit.elapsed += time.delta_seconds();
+        let t = (unit.elapsed / UNIT_POLL_INTERVAL.as_secs_f32()).min(1.0);
+        transform.translation = unit.from.lerp(unit.to, t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bevy::prelude::{Entity, IVec3};
+    use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{UnitDefinition, UnitList};
+
+    use crate::{fortress::{memory::MemoryFortress, session::Response, FortressSource}, world::unit::Unit};
+
+    use super::UnitChanges;
+
+    fn unit(id: i32, valid: bool, (x, y, z): (i32, i32, i32)) -> UnitDefinition {
+        UnitDefinition {
+            id,
+            is_valid: Some(valid),
+            pos_x: Some(x),
+            pos_y: Some(y),
+            pos_z: Some(z),
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// Serves `units` from a [`MemoryFortress`] and compares them with the `spawned` unit ids.
+    fn changes(units: Vec<UnitDefinition>, spawned: &[i32]) -> UnitChanges {
+        let mut fortress = MemoryFortress::default();
+        fortress.apply(Response::UnitList(UnitList { creature_list: units, ..Default::default() }));
+        let spawned = spawned.iter().map(|id| (*id, Entity::from_raw(*id as u32))).collect::<BTreeMap<_, _>>();
+
+        UnitChanges::new(&fortress.get_unit_list(), &spawned)
+    }
+
+    #[test]
+    fn new_units_are_spawned_and_invalid_ones_dropped(){
+        //ARRANGE
+        let units = vec![
+            unit(1, true, (4, 5, 6)),
+            unit(2, false, (4, 5, 6)),
+            unit(3, true, (-30000, -30000, -30000)),
+            unit(4, true, (4, -1, 6)),
+        ];
+
+        //ACT
+        let changes = changes(units, &[]);
+
+        //ASSERT
+        assert_eq!(changes.appeared, vec![Unit { id: 1, position: IVec3::new(4, 5, 6), race: -1 }]);
+        assert!(changes.moved.is_empty());
+        assert!(changes.gone.is_empty());
+    }
+
+    #[test]
+    fn spawned_units_move_and_missing_or_dead_ones_despawn(){
+        //ARRANGE
+        let units = vec![
+            unit(1, true, (7, 5, 6)),
+            unit(2, false, (4, 5, 6)),
+        ];
+
+        //ACT
+        let changes = changes(units, &[1, 2, 3]);
+
+        //ASSERT
+        assert!(changes.appeared.is_empty());
+        assert_eq!(changes.moved, vec![Unit { id: 1, position: IVec3::new(7, 5, 6), race: -1 }]);
+        assert_eq!(changes.gone, vec![2, 3]);
+    }
+}
@@ -0,0 +1,145 @@
+use bevy::prelude::{Color, IVec3, Vec2, Vec3};
+
+use crate::{loaders::model_loader::{create_quad, Direction}, voxel::model_storage::ModelRegistry, world::{tile::{Liquid, Tile}, MaterialRegistry}};
+
+use super::{snapshot::ChunkSnapshot, MeshBuilder};
+
+const SIDES: [Direction; 4] = [Direction::Left, Direction::Right, Direction::Forward, Direction::Backwards];
+
+///
+/// Height of the liquid surface above the bottom of the tile, if the tile holds the same liquid.
+fn surface(tile: Option<&Tile>, liquid: Liquid) -> f32 {
+    match tile.and_then(|x| x.liquid()) {
+        Some((kind, depth)) if kind == liquid => depth as f32 / 7.0,
+        _ => 0.0,
+    }
+}
+
+///
+/// Meshes the liquid in the tile at `pos` as a box whose height follows the depth.
+/// Faces touching the same liquid are culled, side faces only show the part above the neighbouring surface.
+pub fn mesh_liquid(
+    builder: &mut MeshBuilder,
+    chunk: &ChunkSnapshot,
+    pos: IVec3,
+    liquid: Liquid,
+    depth: u8,
+    registry: &MaterialRegistry,
+    models: &ModelRegistry,
+){
+    let height = depth as f32 / 7.0;
+    let offset = pos.as_vec3();
+
+    let mut push = |direction: Direction, size: Vec2, position: Vec3|{
+        let (verts, uvs, normal) = create_quad(direction, size, position, 0.0);
+        builder.push_quad(&verts, &uvs, normal, Color::WHITE, offset);
+    };
+
+    let neighbour = |direction: Direction| -> IVec3{
+        pos + direction.get_coords()[1].as_ivec3()
+    };
+
+    let above = neighbour(Direction::Up);
+    let covered = depth == 7 && chunk.is_solid(above.x, above.y, above.z, Direction::Up, registry, models);
+    if surface(chunk.tile(above.x, above.y, above.z), liquid) == 0.0 && !covered{
+        push(Direction::Up, Vec2::ONE, Vec3::new(0.0, height - 0.5, 0.0));
+    }
+
+    let below = neighbour(Direction::Down);
+    let has_floor = chunk.tile(pos.x, pos.y, pos.z).map_or(false, |x| registry.is_solid(x, Direction::Up, models));
+    if surface(chunk.tile(below.x, below.y, below.z), liquid) < 1.0
+        && !has_floor
+        && !chunk.is_solid(below.x, below.y, below.z, Direction::Down, registry, models){
+        push(Direction::Down, Vec2::ONE, Vec3::new(0.0, -0.5, 0.0));
+    }
+
+    for direction in SIDES{
+        let side = neighbour(direction);
+        if chunk.is_solid(side.x, side.y, side.z, direction, registry, models){
+            continue;
+        }
+
+        let low = surface(chunk.tile(side.x, side.y, side.z), liquid);
+        if low >= height{
+            continue;
+        }
+
+        let center = direction.get_coords()[1] * 0.5 + Vec3::new(0.0, (low + height) / 2.0 - 0.5, 0.0);
+        push(direction, Vec2::new(1.0, height - low), center);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::IVec3;
+    use df_rust::clients::remote_fortress_reader::remote_fortress_reader::TiletypeShape;
+
+    use crate::{loaders::model_loader::Direction, voxel::model_storage::ModelRegistry, world::{meshing::{build_mesh, snapshot::ChunkSnapshot, testing::{registry, tiletype}, MeshBuilder, MeshLayer}, tile::{Liquid, Tile}, MaterialRegistry, World}};
+
+    use super::mesh_liquid;
+
+    fn empty_registry() -> MaterialRegistry {
+        registry(vec![tiletype(0, TiletypeShape::Empty, None)], Vec::new())
+    }
+
+    ///
+    /// Meshes the water at `pos` with `tiles` placed in chunk (0, 0, 0).
+    fn mesh_water(pos: IVec3, tiles: &[(IVec3, Tile)]) -> MeshBuilder {
+        let mut world = World::new();
+        let chunk = world.chunk_mut((0, 0, 0));
+        for (at, tile) in tiles {
+            chunk.set_tile(at.x, at.y, at.z, *tile);
+        }
+        let depth = chunk.tile_ref(pos.x, pos.y, pos.z).water;
+
+        let mut builder = MeshBuilder::default();
+        mesh_liquid(&mut builder, &ChunkSnapshot::new(&world, (0, 0, 0)), pos, Liquid::Water, depth, &empty_registry(), &ModelRegistry::new());
+        builder
+    }
+
+    #[test]
+    fn faces_touching_the_same_liquid_are_culled() {
+        //ARRANGE
+        let water = Tile { water: 7, ..Default::default() };
+        let pos = IVec3::new(4, 4, 4);
+
+        //ACT
+        let builder = mesh_water(pos, &[(pos, water), (pos + IVec3::X, water)]);
+
+        //ASSERT
+        assert!(!builder.normals.contains(&Direction::Left.get_coords()[1]));
+        assert_eq!(builder.verts.len(), 5 * 4);
+    }
+
+    #[test]
+    fn surface_height_follows_the_depth() {
+        //ARRANGE
+        let pos = IVec3::new(4, 4, 4);
+        let cases = [(1, 1.0 / 7.0), (4, 4.0 / 7.0), (7, 1.0)];
+
+        for (depth, height) in cases {
+            //ACT
+            let builder = mesh_water(pos, &[(pos, Tile { water: depth, ..Default::default() })]);
+
+            //ASSERT
+            let top = builder.verts.iter().map(|x| x.y).fold(f32::MIN, f32::max);
+            assert!((top - (pos.y as f32 - 0.5 + height)).abs() < 0.0001, "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn magma_mixed_with_water_is_meshed_as_magma() {
+        //ARRANGE
+        let mut world = World::new();
+        world.chunk_mut((0, 0, 0)).set_tile(4, 4, 4, Tile { water: 3, magma: 2, ..Default::default() });
+        world.chunk_mut((0, 0, 0)).set_tile(8, 4, 4, Tile { water: 3, ..Default::default() });
+
+        //ACT
+        let layers = build_mesh(&ChunkSnapshot::new(&world, (0, 0, 0)), &empty_registry(), &ModelRegistry::new());
+
+        //ASSERT
+        assert_eq!(layers[MeshLayer::Magma.index()].count_vertices(), 6 * 4);
+        assert_eq!(layers[MeshLayer::Water.index()].count_vertices(), 6 * 4);
+        assert_eq!(layers[MeshLayer::Solid.index()].count_vertices(), 0);
+    }
+}
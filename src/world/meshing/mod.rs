@@ -1,26 +1,173 @@
-use bevy::{prelude::{Mesh, Vec3, IVec3, Vec2}, render::mesh::Indices};
+use bevy::{prelude::{Mesh, Vec3, IVec3, Vec2, Handle, StandardMaterial, Color}, render::{mesh::Indices, render_resource::PrimitiveTopology}};
 
-use crate::voxel::model_storage::ModelRegistry;
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::TiletypeShape;
 
-use super::{MaterialRegistry, World, Chunk};
+use crate::{voxel::{model_storage::ModelRegistry, ModelEntry}, loaders::model_loader::BakedModel, world::events::chunk_builder::{VOXEL_MATERIAL, TRANSPARENT_MATERIAL, WATER_MATERIAL, MAGMA_MATERIAL}};
 
+use self::{snapshot::ChunkSnapshot, liquid::mesh_liquid, ramp::mesh_ramp};
+
+use super::{MaterialRegistry, tile::Liquid};
+
+pub mod liquid;
+pub mod ramp;
+pub mod snapshot;
+#[cfg(test)]
+mod testing;
+
+pub const LAYER_COUNT: usize = 4;
+
+///
+/// Each chunk is split into one mesh per layer, every layer drawn with its own material.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MeshLayer {
+    Solid,
+    /// Models marked `Transparent(true)`, alpha blended instead of cut out.
+    Transparent,
+    Water,
+    Magma,
+}
+
+impl MeshLayer {
+    pub const ALL: [MeshLayer; LAYER_COUNT] = [MeshLayer::Solid, MeshLayer::Transparent, MeshLayer::Water, MeshLayer::Magma];
+
+    pub fn material(self) -> Handle<StandardMaterial> {
+        match self {
+            MeshLayer::Solid => VOXEL_MATERIAL.typed(),
+            MeshLayer::Transparent => TRANSPARENT_MATERIAL.typed(),
+            MeshLayer::Water => WATER_MATERIAL.typed(),
+            MeshLayer::Magma => MAGMA_MATERIAL.typed(),
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn for_model(model: &ModelEntry) -> Self {
+        if model.0.transparent {
+            MeshLayer::Transparent
+        }
+        else {
+            MeshLayer::Solid
+        }
+    }
+}
+
+impl From<Liquid> for MeshLayer {
+    fn from(liquid: Liquid) -> Self {
+        match liquid {
+            Liquid::Water => MeshLayer::Water,
+            Liquid::Magma => MeshLayer::Magma,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MeshBuilder {
+    verts: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    normals: Vec<Vec3>,
+    colors: Vec<[f32; 4]>,
+    /// u32, a chunk full of meshes and SDF models easily has more vertices than u16 can index.
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    pub fn push_quad(&mut self, verts: &[Vec3; 4], uvs: &[Vec2; 4], normal: Vec3, color: Color, offset: Vec3) {
+        let c = self.next_index();
+        self.verts.extend(verts.iter().map(|x| *x + offset));
+        self.uvs.extend(uvs);
+        self.normals.extend([normal; 4]);
+        self.colors.extend([color.as_linear_rgba_f32(); 4]);
+        self.indices.extend([
+            c, c + 2, c + 1,
+            c + 2,    c + 3, c + 1
+        ]);
+    }
+
+    ///
+    /// `colors` are per vertex and multiplied into `color`.
+    pub fn push_mesh(&mut self, data: &[(Vec3, Vec2, Vec3)], colors: Option<&[[f32; 4]]>, indices: &[u16], color: Color, offset: Vec3) {
+        let c = self.next_index();
+        let color = color.as_linear_rgba_f32();
+        for (i, (v, u, n)) in data.iter().enumerate() {
+            self.verts.push(*v + offset);
+            self.uvs.push(*u);
+            self.normals.push(*n);
+            match colors.and_then(|x| x.get(i)) {
+                Some(vertex) => self.colors.push([0, 1, 2, 3].map(|x| color[x] * vertex[x])),
+                None => self.colors.push(color),
+            }
+        }
+        self.indices.extend(indices.iter().map(|x| *x as u32 + c));
+    }
+
+    fn next_index(&self) -> u32 {
+        u32::try_from(self.verts.len()).expect("mesh has more vertices than u32 can index")
+    }
+
+    pub fn into_mesh(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.verts);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh.compute_aabb();
+        mesh
+    }
+}
+
+///
+/// Meshes a model on its own, with nothing around it to cull against.
+/// `color` is only applied if the model is tinted.
+pub fn build_model_mesh(model: &ModelEntry, color: Color) -> Mesh {
+    let mut builder = MeshBuilder::default();
+    let color = if model.0.tint { color } else { Color::WHITE };
+    model.0.models.iter().filter(|x| x.cullable().is_visible(0)).for_each(|x|{
+        match x {
+            BakedModel::Quad { verts:v, uvs:u, normal:n, cullable:_ } => {
+                builder.push_quad(v, u, *n, color, Vec3::ZERO);
+            },
+            BakedModel::Mesh { data, colors, indices:i, cullable:_ } => {
+                builder.push_mesh(data, colors.as_deref(), i, color, Vec3::ZERO);
+            },
+        }
+    });
+    builder.into_mesh()
+}
+
+///
+/// Only reads its arguments, so it can run on the task pool.
+/// Returns one mesh per [`MeshLayer`], in the order of [`MeshLayer::ALL`].
 pub fn build_mesh(
-    mesh: &mut Mesh,
-    chunk: &Chunk,
+    chunk: &ChunkSnapshot,
     registry: &MaterialRegistry,
-    models: &mut ModelRegistry){
-    let mut verts = Vec::<Vec3>::new();
-    let mut uvs = Vec::<Vec2>::new();
-    let mut normals = Vec::<Vec3>::new();
-    let mut indices = Vec::<u16>::new();
+    models: &ModelRegistry) -> [Mesh; LAYER_COUNT]{
+    let mut layers: [MeshBuilder; LAYER_COUNT] = Default::default();
 
     for x in 0..16{
         for y in 0..16{
             for z in 0..16{
-                let tile = chunk.tile_ref(x, y, z);
-                
+                let Some(tile) = chunk.tile(x, y, z) else{
+                    continue;
+                };
+
                 if !tile.hidden{
                     let pos = IVec3::new(x,y,z).as_vec3();
+
+                    if let Some((liquid, depth)) = tile.liquid(){
+                        mesh_liquid(
+                            &mut layers[MeshLayer::from(liquid).index()],
+                            chunk,
+                            IVec3::new(x, y, z),
+                            liquid,
+                            depth,
+                            registry,
+                            models
+                        );
+                    }
+
                     let type_ = registry.get_tiletype(tile);
 
                     let mat_pair = &tile.base_mat;
@@ -28,32 +175,31 @@ pub fn build_mesh(
                         else{
                             continue;
                         };
-                    let id = def.id.as_ref().unwrap();
+                    let Some(id) = def.id.as_ref() else{
+                        continue;
+                    };
 
                     let Some(model) = models.get_model_and_cache(id, type_.shape) else{ continue; };
+                    let color = if model.0.tint { def.color } else { Color::WHITE };
+
+                    //Ramp geometry depends on the neighbours, the model only provides the texture
+                    if type_.shape == TiletypeShape::Ramp{
+                        if let Some(part) = model.0.models.first(){
+                            mesh_ramp(&mut layers[MeshLayer::for_model(model).index()], chunk, IVec3::new(x, y, z), part.uv_bounds(), color, registry, models);
+                        }
+                        continue;
+                    }
                     
-                    let mask = chunk.get_mask(x, y, z, &registry);
+                    let mask = chunk.get_mask(x, y, z, registry, models);
+                    let layer = &mut layers[MeshLayer::for_model(model).index()];
 
                     model.0.models.iter().filter(|x| x.cullable().is_visible(mask)).for_each(|x|{
                         match x {
-                            crate::loaders::model_loader::BakedModel::Quad { verts:v, uvs:u, normal:n, cullable:_ } => {
-                                let c = verts.len() as u16;
-                                verts.extend(v.iter().map(|x| *x + pos));
-                                uvs.extend(u);
-                                normals.extend([*n;4]);
-                                indices.extend([
-                                    c + 0u16, c + 2, c + 1,
-                                    c + 2,    c + 3, c + 1
-                                ]);
+                            BakedModel::Quad { verts:v, uvs:u, normal:n, cullable:_ } => {
+                                layer.push_quad(v, u, *n, color, pos);
                             },
-                            crate::loaders::model_loader::BakedModel::Mesh { data, indices:i, cullable:_ } => {
-                                let c = verts.len() as u16;
-                                for (v, u, n) in data{
-                                    verts.push(*v + pos);
-                                    uvs.push(*u);
-                                    normals.push(*n);
-                                }
-                                indices.extend(i.iter().map(|x| *x + c));
+                            BakedModel::Mesh { data, colors, indices:i, cullable:_ } => {
+                                layer.push_mesh(data, colors.as_deref(), i, color, pos);
                             },
                             
                         }
@@ -62,9 +208,115 @@ pub fn build_mesh(
             }
         }
     }
-    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, verts);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL,  normals);
-    mesh.set_indices(Some(Indices::U16(indices)));
-    mesh.compute_aabb();
-}
\ No newline at end of file
+
+    layers.map(MeshBuilder::into_mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{prelude::{Color, Mesh, Vec3}, render::mesh::{Indices, VertexAttributeValues}};
+    use df_rust::clients::remote_fortress_reader::remote_fortress_reader::TiletypeShape;
+    use model_system::{format::Bound, sdf::{surface_nets, Sdf}};
+
+    use crate::{voxel::model_storage::ModelRegistry, world::{tile::{material_identifier::Identifier, Tile}, MaterialDef, Matpair, World}};
+
+    use super::{build_mesh, snapshot::ChunkSnapshot, testing::{cube, registry, tiletype}, MeshBuilder, MeshLayer};
+
+    const WALL: i32 = 1;
+    const GLASS: Matpair = Matpair { type_: 3, index: 0 };
+    const STONE: Matpair = Matpair { type_: 0, index: 1 };
+    const COPPER: Matpair = Matpair { type_: 0, index: 2 };
+
+    fn colors(mesh: &Mesh) -> Vec<[f32; 4]> {
+        match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float32x4(colors)) => colors.clone(),
+            _ => panic!("chunk meshes have an rgba colour per vertex"),
+        }
+    }
+
+    #[test]
+    fn full_chunk_of_sdf_tiles_indexes_every_vertex() {
+        //ARRANGE
+        let sdf = surface_nets(&Sdf::Sphere{ center: Vec3::ZERO, radius: 0.45 }, &Bound::default());
+        let data = sdf.verts.iter().zip(&sdf.uvs).zip(&sdf.normals).map(|((v, u), n)| (*v, *u, *n)).collect::<Vec<_>>();
+        let mut builder = MeshBuilder::default();
+
+        //ACT
+        for z in 0..16 {
+            for y in 0..16 {
+                for x in 0..16 {
+                    builder.push_mesh(&data, None, &sdf.indices, Color::WHITE, Vec3::new(x as f32, y as f32, z as f32));
+                }
+            }
+        }
+        let mesh = builder.into_mesh();
+
+        //ASSERT
+        let Some(Indices::U32(indices)) = mesh.indices() else{
+            panic!("chunk meshes are indexed with u32");
+        };
+        let count = mesh.count_vertices();
+        assert!(count > u16::MAX as usize);
+        assert_eq!(indices.len(), 16 * 16 * 16 * sdf.indices.len());
+        assert_eq!(indices.iter().max().map(|x| *x as usize), Some(count - 1));
+    }
+
+    #[test]
+    fn faces_between_tiles_of_the_same_transparent_model_are_culled() {
+        //ARRANGE
+        let registry = registry(
+            vec![tiletype(0, TiletypeShape::Empty, None), tiletype(WALL, TiletypeShape::Wall, None)],
+            vec![
+                MaterialDef { id: Some(Identifier::from("GLASS".to_owned())), mat_pair: GLASS, color: Color::WHITE },
+                MaterialDef { id: Some(Identifier::from("STONE".to_owned())), mat_pair: STONE, color: Color::WHITE },
+            ],
+        );
+        let mut models = ModelRegistry::new();
+        models.get_storage_entry_mut(TiletypeShape::Wall).add_model(cube(true, false), Identifier::from("GLASS".to_owned()));
+        models.get_storage_entry_mut(TiletypeShape::Wall).add_model(cube(false, false), Identifier::from("STONE".to_owned()));
+
+        let mut world = World::new();
+        let chunk = world.chunk_mut((0, 0, 0));
+        chunk.set_tile(4, 4, 4, Tile { tile_id: WALL, base_mat: GLASS, ..Default::default() });
+        chunk.set_tile(5, 4, 4, Tile { tile_id: WALL, base_mat: GLASS, ..Default::default() });
+        chunk.set_tile(4, 4, 5, Tile { tile_id: WALL, base_mat: STONE, ..Default::default() });
+
+        //ACT
+        let layers = build_mesh(&ChunkSnapshot::new(&world, (0, 0, 0)), &registry, &models);
+
+        //ASSERT
+        //The panes lose the face between them and the one against the stone, the stone still shows through the glass
+        assert_eq!(layers[MeshLayer::Transparent.index()].count_vertices(), 9 * 4);
+        assert_eq!(layers[MeshLayer::Solid.index()].count_vertices(), 6 * 4);
+    }
+
+    #[test]
+    fn only_tinted_models_take_the_material_colour() {
+        //ARRANGE
+        let copper = Color::rgb(0.8, 0.4, 0.2);
+        let registry = registry(
+            vec![tiletype(0, TiletypeShape::Empty, None), tiletype(WALL, TiletypeShape::Wall, None)],
+            vec![
+                MaterialDef { id: Some(Identifier::from("COPPER".to_owned())), mat_pair: COPPER, color: copper },
+                MaterialDef { id: Some(Identifier::from("STONE".to_owned())), mat_pair: STONE, color: copper },
+            ],
+        );
+        let mut models = ModelRegistry::new();
+        models.get_storage_entry_mut(TiletypeShape::Wall).add_model(cube(false, true), Identifier::from("COPPER".to_owned()));
+        models.get_storage_entry_mut(TiletypeShape::Wall).add_model(cube(false, false), Identifier::from("STONE".to_owned()));
+        let cases = [("tinted", COPPER, copper), ("not tinted", STONE, Color::WHITE)];
+
+        for (name, base_mat, expected) in cases {
+            let mut world = World::new();
+            world.chunk_mut((0, 0, 0)).set_tile(4, 4, 4, Tile { tile_id: WALL, base_mat, ..Default::default() });
+
+            //ACT
+            let layers = build_mesh(&ChunkSnapshot::new(&world, (0, 0, 0)), &registry, &models);
+
+            //ASSERT
+            let colors = colors(&layers[MeshLayer::Solid.index()]);
+            assert_eq!(colors.len(), 6 * 4, "{}", name);
+            assert!(colors.iter().all(|x| *x == expected.as_linear_rgba_f32()), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use bevy::prelude::{Color, IVec3, Vec2, Vec3};
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::TiletypeShape;
+
+use crate::{loaders::model_loader::Direction, voxel::model_storage::ModelRegistry, world::MaterialRegistry};
+
+use super::{snapshot::ChunkSnapshot, MeshBuilder};
+
+/// Corners of the top surface as (x, z) signs, in the order the heights are stored.
+const CORNERS: [(i32, i32); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+/// Side faces with the two corners they span.
+const SIDES: [(Direction, usize, usize); 4] = [
+    (Direction::Left, 1, 3),
+    (Direction::Right, 0, 2),
+    (Direction::Forward, 2, 3),
+    (Direction::Backwards, 0, 1),
+];
+
+fn has_shape(chunk: &ChunkSnapshot, x: i32, y: i32, z: i32, shape: TiletypeShape, registry: &MaterialRegistry) -> bool {
+    chunk.tile(x, y, z).map_or(false, |x| registry.get_tiletype(x).shape == shape)
+}
+
+///
+/// Whether any of the three horizontal neighbours touching a corner has `shape`.
+fn corner_touches(chunk: &ChunkSnapshot, pos: IVec3, (cx, cz): (i32, i32), shape: TiletypeShape, registry: &MaterialRegistry) -> bool {
+    has_shape(chunk, pos.x + cx, pos.y, pos.z, shape, registry)
+        || has_shape(chunk, pos.x, pos.y, pos.z + cz, shape, registry)
+        || has_shape(chunk, pos.x + cx, pos.y, pos.z + cz, shape, registry)
+}
+
+///
+/// Corners raised by the compass letters of a tiletype direction, DF north is towards -z and east towards +x.
+fn direction_heights(direction: &str) -> [bool; 4] {
+    CORNERS.map(|(cx, cz)| direction.chars().any(|x| match x {
+        'N' => cz < 0,
+        'S' => cz > 0,
+        'E' => cx > 0,
+        'W' => cx < 0,
+        _ => false,
+    }))
+}
+
+///
+/// A corner is raised when any of the three horizontal neighbours touching it is a wall.
+/// One wall side gives a straight slope, a lone diagonal wall an outer corner and two wall sides an inner corner.
+/// A ramp without walls around it is walked onto from the floors next to it, so it rises away from them.
+/// Without walls or floors it rises towards the direction of its tiletype, if it has one.
+pub fn corner_heights(chunk: &ChunkSnapshot, pos: IVec3, registry: &MaterialRegistry) -> [bool; 4] {
+    let raised = CORNERS.map(|corner| corner_touches(chunk, pos, corner, TiletypeShape::Wall, registry));
+    if raised.contains(&true){
+        return raised;
+    }
+
+    let low = CORNERS.map(|corner| corner_touches(chunk, pos, corner, TiletypeShape::Floor, registry));
+    if low.contains(&true) && low.contains(&false){
+        return low.map(|x| !x);
+    }
+
+    let direction = chunk.tile(pos.x, pos.y, pos.z).and_then(|x| registry.get_tiletype(x).direction.as_deref());
+    direction.map_or(raised, direction_heights)
+}
+
+///
+/// Pushes a triangle facing `outward`, degenerate triangles are dropped.
+fn push_triangle(builder: &mut MeshBuilder, verts: [Vec3; 3], uvs: [Vec2; 3], outward: Vec3, color: Color, offset: Vec3) {
+    let normal = (verts[1] - verts[0]).cross(verts[2] - verts[0]);
+    if normal.length_squared() < 1e-6{
+        return;
+    }
+
+    let (order, normal) = if normal.dot(outward) < 0.0 {
+        ([0, 2, 1], -normal)
+    }
+    else{
+        ([0, 1, 2], normal)
+    };
+
+    let normal = normal.normalize();
+    let data = order.map(|i| (verts[i], uvs[i], normal));
+    builder.push_mesh(&data, None, &[0, 1, 2], color, offset);
+}
+
+///
+/// Meshes a ramp tile as a slope rising towards the neighbouring walls.
+/// `uv` is the (min, max) rect of the ramp texture in the atlas.
+pub fn mesh_ramp(
+    builder: &mut MeshBuilder,
+    chunk: &ChunkSnapshot,
+    pos: IVec3,
+    uv: (Vec2, Vec2),
+    color: Color,
+    registry: &MaterialRegistry,
+    models: &ModelRegistry,
+){
+    let offset = pos.as_vec3();
+    let raised = corner_heights(chunk, pos, registry);
+    let heights = raised.map(|x| if x { 0.5 } else { -0.5 });
+
+    let corner = |i: usize| -> Vec3{
+        let (cx, cz) = CORNERS[i];
+        Vec3::new(cx as f32 * 0.5, heights[i], cz as f32 * 0.5)
+    };
+    let to_atlas = |x: Vec2| uv.0 + x * (uv.1 - uv.0);
+    let top_uv = |v: Vec3| to_atlas(Vec2::new(v.x + 0.5, v.z + 0.5));
+
+    //Split along the diagonal that leaves out the odd corner, so corners fold instead of forming a saddle
+    let raised_count = raised.iter().filter(|x| **x).count();
+    let odd = match raised_count {
+        1 => raised.iter().position(|x| *x),
+        3 => raised.iter().position(|x| !*x),
+        _ => None,
+    };
+    let triangles = match odd {
+        Some(0) | Some(3) => [[0, 2, 1], [1, 2, 3]],
+        _ => [[0, 3, 1], [0, 2, 3]],
+    };
+
+    for triangle in triangles{
+        let verts = triangle.map(corner);
+        push_triangle(builder, verts, verts.map(top_uv), Vec3::Y, color, offset);
+    }
+
+    for (direction, a, b) in SIDES{
+        let side = pos + direction.get_coords()[1].as_ivec3();
+        if chunk.is_solid(side.x, side.y, side.z, direction, registry, models){
+            continue;
+        }
+
+        let (top_a, top_b) = (corner(a), corner(b));
+        let (bottom_a, bottom_b) = (Vec3::new(top_a.x, -0.5, top_a.z), Vec3::new(top_b.x, -0.5, top_b.z));
+
+        let normal = direction.get_coords()[1];
+        //Horizontal texture coordinate runs along whichever axis the side spans
+        let side_uv = |v: Vec3| to_atlas(Vec2::new(if normal.x != 0.0 { v.z + 0.5 } else { v.x + 0.5 }, 0.5 - v.y));
+
+        for verts in [[bottom_a, top_a, bottom_b], [bottom_b, top_a, top_b]]{
+            push_triangle(builder, verts, verts.map(side_uv), normal, color, offset);
+        }
+    }
+
+    let below = pos + IVec3::NEG_Y;
+    if !chunk.is_solid(below.x, below.y, below.z, Direction::Down, registry, models){
+        let verts = [0, 1, 2, 3].map(|i|{
+            let (cx, cz) = CORNERS[i];
+            Vec3::new(cx as f32 * 0.5, -0.5, cz as f32 * 0.5)
+        });
+        for triangle in [[0, 1, 3], [0, 3, 2]]{
+            let verts = triangle.map(|i| verts[i]);
+            push_triangle(builder, verts, verts.map(top_uv), Vec3::NEG_Y, color, offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::IVec3;
+    use df_rust::clients::remote_fortress_reader::remote_fortress_reader::TiletypeShape;
+
+    use crate::world::{meshing::{snapshot::ChunkSnapshot, testing::{registry, tiletype}}, tile::Tile, MaterialRegistry, World};
+
+    use super::corner_heights;
+
+    const WALL: i32 = 1;
+    const RAMP: i32 = 2;
+    const NORTH_RAMP: i32 = 3;
+    const FLOOR: i32 = 4;
+
+    fn ramp_registry() -> MaterialRegistry {
+        registry(vec![
+            tiletype(0, TiletypeShape::Empty, None),
+            tiletype(WALL, TiletypeShape::Wall, None),
+            tiletype(RAMP, TiletypeShape::Ramp, Some("--------")),
+            tiletype(NORTH_RAMP, TiletypeShape::Ramp, Some("N")),
+            tiletype(FLOOR, TiletypeShape::Floor, None),
+        ], Vec::new())
+    }
+
+    ///
+    /// Places `tiles` around the origin of chunk (0, 0, 0), offsets may reach into the chunks next to it.
+    fn heights(pos: IVec3, tiles: &[(IVec3, i32)]) -> [bool; 4] {
+        let mut world = World::new();
+        world.chunk_mut((0, 0, 0));
+        world.chunk_mut((1, 0, 0));
+        for (offset, tile_id) in tiles {
+            let at = pos + *offset;
+            let key = (at.x.div_euclid(16), at.y.div_euclid(16), at.z.div_euclid(16));
+            let tile = Tile { tile_id: *tile_id, ..Default::default() };
+            world.chunk_mut(key).set_tile(at.x.rem_euclid(16), at.y.rem_euclid(16), at.z.rem_euclid(16), tile);
+        }
+
+        corner_heights(&ChunkSnapshot::new(&world, (0, 0, 0)), pos, &ramp_registry())
+    }
+
+    #[test]
+    fn corners_rise_towards_the_walls_around_the_ramp() {
+        //ARRANGE
+        let inside = IVec3::new(4, 4, 4);
+        let border = IVec3::new(15, 4, 4);
+        let cases = [
+            ("straight", inside, vec![(IVec3::ZERO, RAMP), (IVec3::X, WALL)], [false, true, false, true]),
+            ("outer corner", inside, vec![(IVec3::ZERO, RAMP), (IVec3::new(1, 0, 1), WALL)], [false, false, false, true]),
+            ("inner corner", inside, vec![(IVec3::ZERO, RAMP), (IVec3::X, WALL), (IVec3::Z, WALL)], [false, true, true, true]),
+            ("across the chunk border", border, vec![(IVec3::ZERO, RAMP), (IVec3::X, WALL)], [false, true, false, true]),
+            ("no walls", inside, vec![(IVec3::ZERO, RAMP)], [false, false, false, false]),
+            ("no walls with a direction", inside, vec![(IVec3::ZERO, NORTH_RAMP)], [true, true, false, false]),
+            ("walls win over the direction", inside, vec![(IVec3::ZERO, NORTH_RAMP), (IVec3::NEG_X, WALL)], [true, false, true, false]),
+            ("away from a floor", inside, vec![(IVec3::ZERO, RAMP), (IVec3::X, FLOOR)], [true, false, true, false]),
+            ("floors win over the direction", inside, vec![(IVec3::ZERO, NORTH_RAMP), (IVec3::NEG_Z, FLOOR)], [false, false, true, true]),
+            ("walls win over floors", inside, vec![(IVec3::ZERO, RAMP), (IVec3::X, WALL), (IVec3::NEG_X, FLOOR)], [false, true, false, true]),
+        ];
+
+        for (name, pos, tiles, expected) in cases {
+            //ACT
+            let raised = heights(pos, &tiles);
+
+            //ASSERT
+            assert_eq!(raised, expected, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{loaders::model_loader::Direction, voxel::model_storage::ModelRegistry, world::{tile::Tile, World, MaterialRegistry}};
+
+const SIZE: i32 = 18;
+
+///
+/// Copy of a chunk's tiles plus a one tile border from its neighbours, so the chunk can be meshed off the main thread.
+/// Coordinates go from -1 to 16, tiles in neighbours that aren't loaded are None.
+pub struct ChunkSnapshot {
+    tiles: Box<[Option<Tile>]>,
+}
+
+impl ChunkSnapshot {
+    pub fn new(world: &World, key: (i32, i32, i32)) -> Self {
+        let mut tiles = Vec::with_capacity((SIZE * SIZE * SIZE) as usize);
+        for z in -1..SIZE - 1 {
+            for y in -1..SIZE - 1 {
+                for x in -1..SIZE - 1 {
+                    tiles.push(world.tile_at(key, x, y, z).copied());
+                }
+            }
+        }
+
+        Self {
+            tiles: tiles.into_boxed_slice(),
+        }
+    }
+
+    pub fn tile(&self, x: i32, y: i32, z: i32) -> Option<&Tile> {
+        if x < -1 || x >= SIZE - 1 || y < -1 || y >= SIZE - 1 || z < -1 || z >= SIZE - 1 {
+            return None;
+        }
+        self.tiles[((x + 1) + (y + 1) * SIZE + (z + 1) * SIZE * SIZE) as usize].as_ref()
+    }
+
+    pub fn is_solid(&self, x: i32, y: i32, z: i32, direction: Direction, registry: &MaterialRegistry, models: &ModelRegistry) -> bool {
+        match self.tile(x, y, z) {
+            Some(tile) => registry.is_solid(tile, direction, models),
+            None => false,
+        }
+    }
+
+    ///
+    /// Bits of the neighbours hiding the tile's faces, a neighbour with the same transparent model hides them too.
+    pub fn get_mask(&self, x: i32, y: i32, z: i32, registry: &MaterialRegistry, models: &ModelRegistry) -> u8 {
+        let tile = self.tile(x, y, z);
+        let hides = |x: i32, y: i32, z: i32, direction: Direction| {
+            self.is_solid(x, y, z, direction, registry, models) || match (tile, self.tile(x, y, z)) {
+                (Some(tile), Some(neighbour)) => registry.shares_transparent_model(tile, neighbour, models),
+                _ => false,
+            }
+        };
+
+        let up = hides(x, y+1, z, Direction::Up);
+        let down = hides(x, y-1, z, Direction::Down);
+        let left = hides(x+1, y, z, Direction::Left);
+        let right = hides(x-1, y, z, Direction::Right);
+        let forward = hides(x, y, z+1, Direction::Forward);
+        let backward = hides(x, y, z-1, Direction::Backwards);
+
+        ((up as u8)      << 5) |
+        ((down as u8)    << 4) |
+        ((left as u8)    << 3) |
+        ((right as u8)   << 2) |
+        ((forward as u8) << 1) |
+        ((backward as u8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use df_rust::clients::remote_fortress_reader::remote_fortress_reader::TiletypeShape;
+
+    use crate::{voxel::model_storage::ModelRegistry, world::{meshing::testing::{registry, tiletype}, tile::Tile, World}};
+
+    use super::ChunkSnapshot;
+
+    const WALL: i32 = 1;
+    /// Bit of [`ChunkSnapshot::get_mask`] for the neighbour at x+1.
+    const LEFT: u8 = 1 << 3;
+
+    #[test]
+    fn faces_between_chunks_are_culled_until_the_neighbour_is_unloaded() {
+        //ARRANGE
+        let registry = registry(vec![tiletype(0, TiletypeShape::Empty, None), tiletype(WALL, TiletypeShape::Wall, None)], Vec::new());
+        let models = ModelRegistry::new();
+        let wall = Tile { tile_id: WALL, ..Default::default() };
+        let mut world = World::new();
+        world.chunk_mut((0, 0, 0)).set_tile(15, 0, 0, wall);
+        world.chunk_mut((1, 0, 0)).set_tile(0, 0, 0, wall);
+
+        //ACT
+        let loaded = ChunkSnapshot::new(&world, (0, 0, 0)).get_mask(15, 0, 0, &registry, &models);
+        world.remove_chunk((1, 0, 0));
+        let unloaded = ChunkSnapshot::new(&world, (0, 0, 0)).get_mask(15, 0, 0, &registry, &models);
+
+        //ASSERT
+        assert_eq!(loaded & LEFT, LEFT);
+        assert_eq!(unloaded & LEFT, 0);
+    }
+}
@@ -0,0 +1,44 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use bevy::prelude::Vec2;
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{Tiletype, TiletypeShape};
+
+use crate::{loaders::model_loader::{create_quad, BakedModel, Cullable, Direction}, voxel::{ModelData, ModelEntry}, world::{FixedTiletype, MaterialDef, MaterialRegistry}};
+
+const DIRECTIONS: [Direction; 6] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right, Direction::Forward, Direction::Backwards];
+
+///
+/// A tiletype as DFHack would send it, `direction` is the ramp direction string.
+pub fn tiletype(id: i32, shape: TiletypeShape, direction: Option<&str>) -> FixedTiletype {
+    FixedTiletype::from(Tiletype {
+        id,
+        shape: Some(shape as i32),
+        direction: direction.map(|x| x.as_bytes().to_vec()),
+        ..Default::default()
+    })
+}
+
+///
+/// `tiletypes` are indexed by tile id, so they have to be listed in order.
+pub fn registry(tiletypes: Vec<FixedTiletype>, matdefs: Vec<MaterialDef>) -> MaterialRegistry {
+    MaterialRegistry {
+        matdefs: Arc::new(matdefs.into_iter().map(|x| (x.mat_pair, x)).collect::<BTreeMap<_, _>>()),
+        tiletypes: Arc::new(tiletypes),
+    }
+}
+
+///
+/// A full tile cube, each face culled when the neighbour in front of it hides it.
+pub fn cube(transparent: bool, tint: bool) -> ModelEntry {
+    let models = DIRECTIONS.into_iter().map(|direction| {
+        let (verts, uvs, normal) = create_quad(direction, Vec2::ONE, direction.get_coords()[1] * 0.5, 0.0);
+        BakedModel::Quad { verts, uvs, normal, cullable: Cullable::WhenVisible(direction) }
+    }).collect();
+
+    ModelEntry(ModelData {
+        models,
+        transparent,
+        tint,
+        visibility: BTreeMap::new(),
+    })
+}
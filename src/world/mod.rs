@@ -1,28 +1,304 @@
-use std::{collections::{btree_map::Entry, BTreeMap}, fs::File};
+use std::{collections::{btree_map::Entry, BTreeMap, BTreeSet}, fs::File, sync::Arc};
 use std::io::Write;
-use bevy::{prelude::{Component, Entity, FromWorld, Resource}};
-use df_rust::clients::remote_fortress_reader::{RemoteFortressReader, remote_fortress_reader::{MatPair, TiletypeMaterial, TiletypeShape, TiletypeSpecial, TiletypeVariant, Tiletype}};
+use bevy::{prelude::{Color, Component, Entity, FromWorld, Resource, Handle, Mesh}};
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{MatPair, TiletypeMaterial, TiletypeShape, TiletypeSpecial, TiletypeVariant, Tiletype, MapBlock};
 
-use crate::{FortressResource, loaders::model_loader::Direction};
+use crate::{fortress::FortressResource, loaders::model_loader::Direction, voxel::{model_storage::ModelRegistry, ModelEntry}};
 
-use self::tile::{Tile, material_identifier::{MaterialIdentifier, Identifier}};
+use self::{tile::{Tile, material_identifier::{MaterialIdentifier, Identifier}}, meshing::{LAYER_COUNT, MeshLayer}, building::Building};
 
+pub mod building;
 pub mod events;
 pub mod tile;
 pub mod meshing;
+pub mod unit;
 
 #[derive(Resource)]
 pub struct World {
     chunks: BTreeMap<(i32, i32, i32), Box<Chunk>>,
+    dirty: BTreeSet<(i32, i32, i32)>,
+    buildings: BTreeMap<i32, Building>,
+    /// Indices of buildings that appeared, changed or disappeared since the last sync.
+    changed_buildings: BTreeSet<i32>,
+    /// Chunks that were unloaded after DFHack sent them, DFHack won't send their unchanged blocks again.
+    /// Holds at most [`MAX_REMOVED_CHUNKS`], the ones unloaded longest ago are dropped first.
+    removed: BTreeMap<(i32, i32, i32), RemovedChunk>,
+    /// Bumped every time a chunk is parked, orders the entries of `removed`.
+    parked: u64,
+    /// Chunks dropped from `removed`, their blocks have to be sent again before they can be loaded.
+    evicted: BTreeSet<(i32, i32, i32)>,
+}
+
+/// Unloaded chunks kept around, about 100 kB each.
+const MAX_REMOVED_CHUNKS: usize = 512;
+
+///
+/// How much of a block DFHack sent.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlockContents {
+    /// The first time DFHack sends a block everything in it is included, an empty building list means there are none.
+    Full,
+    /// Afterwards only the parts that changed are, buildings are left out unless they changed.
+    Changes,
+}
+
+///
+/// The tiles and buildings of an unloaded chunk, kept so it can be shown again without resending the whole map.
+struct RemovedChunk {
+    chunk: Box<Chunk>,
+    buildings: Vec<Building>,
+    parked: u64,
 }
 
 impl World {
     pub fn new() -> Self {
         Self {
             chunks: BTreeMap::new(),
+            dirty: BTreeSet::new(),
+            buildings: BTreeMap::new(),
+            changed_buildings: BTreeSet::new(),
+            removed: BTreeMap::new(),
+            parked: 0,
+            evicted: BTreeSet::new(),
+        }
+    }
+
+    ///
+    /// Key of the chunk covering a DFHack map block.
+    /// Chunks are 16 z-levels tall with x/z/y flipped into x/y/z.
+    pub fn block_key(block: &MapBlock) -> (i32, i32, i32) {
+        (block.map_x.div_euclid(16), block.map_z.div_euclid(16), block.map_y.div_euclid(16))
+    }
+
+    ///
+    /// Copies a DFHack map block into the chunk covering it, and returns the key of that chunk.
+    /// DFHack leaves out the tiles of a block whose tiles didn't change, those tiles keep their current values.
+    /// A block of a chunk that isn't loaded is kept for when that chunk is restored,
+    /// unless the chunk was evicted, its whole blocks are sent again once the map hashes are reset.
+    pub fn apply_block(&mut self, block: &MapBlock, contents: BlockContents) -> (i32, i32, i32) {
+        let key = Self::block_key(block);
+        let y = block.map_z.rem_euclid(16);
+
+        if self.evicted.contains(&key) {
+            return key;
+        }
+
+        let unloaded = !self.chunks.contains_key(&key);
+        if unloaded {
+            self.restore_chunk(key);
+        }
+
+        let chunk = self.chunk_mut(key);
+        for x in 0..16 {
+            for z in 0..16 {
+                let id = (x + z * 16) as usize;
+
+                let mut tile = *chunk.tile_ref(x, y, z);
+                if let Some(tile_id) = block.tiles.get(id) {
+                    tile.tile_id = *tile_id;
+                }
+                if let Some(mat_pair) = block.materials.get(id) {
+                    tile.mat_pair = mat_pair.clone().into();
+                }
+                if let Some(base_mat) = block.base_materials.get(id) {
+                    tile.base_mat = base_mat.clone().into();
+                }
+                if let Some(hidden) = block.hidden.get(id) {
+                    tile.hidden = *hidden;
+                }
+                if let Some(water) = block.water.get(id) {
+                    tile.water = *water as u8;
+                }
+                if let Some(magma) = block.magma.get(id) {
+                    tile.magma = *magma as u8;
+                }
+
+                chunk.set_tile(x, y, z, tile)
+            }
+        }
+
+        if contents == BlockContents::Full || Self::has_buildings(block) {
+            self.apply_buildings(block);
+        }
+        if unloaded {
+            self.park_chunk(key);
+        }
+        key
+    }
+
+    ///
+    /// Whether a changed block carries its buildings.
+    /// DFHack only sends them when they changed, a block sent for nothing but its buildings has no other data,
+    /// so an empty building list in it means the last building was removed.
+    fn has_buildings(block: &MapBlock) -> bool {
+        !block.buildings.is_empty() || (
+            block.tiles.is_empty()
+            && block.materials.is_empty()
+            && block.base_materials.is_empty()
+            && block.hidden.is_empty()
+            && block.water.is_empty()
+            && block.magma.is_empty()
+        )
+    }
+
+    ///
+    /// Replaces the buildings owned by this block, a building missing from the block has been removed.
+    fn apply_buildings(&mut self, block: &MapBlock) {
+        let incoming = block.buildings
+            .iter()
+            .map(Building::from)
+            .filter(|x| x.is_owned_by(block.map_x, block.map_y, block.map_z))
+            .map(|x| (x.index, x))
+            .collect::<BTreeMap<_, _>>();
+
+        let removed = self.buildings
+            .iter()
+            .filter(|(index, building)| building.is_owned_by(block.map_x, block.map_y, block.map_z) && !incoming.contains_key(index))
+            .map(|(index, _)| *index)
+            .collect::<Vec<_>>();
+
+        for index in removed {
+            self.buildings.remove(&index);
+            self.changed_buildings.insert(index);
+        }
+
+        for (index, building) in incoming {
+            if self.buildings.get(&index) != Some(&building) {
+                self.buildings.insert(index, building);
+                self.changed_buildings.insert(index);
+            }
+        }
+    }
+
+    pub fn get_building(&self, index: i32) -> Option<&Building> {
+        self.buildings.get(&index)
+    }
+
+    ///
+    /// Respawns every building, used when the models they were built from change.
+    pub fn mark_all_buildings_changed(&mut self) {
+        self.changed_buildings.extend(self.buildings.keys());
+    }
+
+    pub fn take_changed_buildings(&mut self) -> Vec<i32> {
+        std::mem::take(&mut self.changed_buildings).into_iter().collect()
+    }
+
+    pub fn mark_dirty(&mut self, key: (i32, i32, i32)) {
+        self.dirty.insert(key);
+    }
+
+    ///
+    /// Marks the chunk and every loaded chunk sharing a face with it,
+    /// since their boundary faces may have been culled against the old tiles.
+    pub fn mark_dirty_with_neighbours(&mut self, key: (i32, i32, i32)) {
+        self.mark_dirty(key);
+        for (dx, dy, dz) in [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)] {
+            let neighbour = (key.0 + dx, key.1 + dy, key.2 + dz);
+            if self.chunks.contains_key(&neighbour) {
+                self.mark_dirty(neighbour);
+            }
+        }
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.extend(self.chunks.keys());
+    }
+
+    pub fn take_dirty(&mut self) -> Vec<(i32, i32, i32)> {
+        std::mem::take(&mut self.dirty).into_iter().collect()
+    }
+
+    ///
+    /// Unloads a chunk and its buildings, its neighbours are remeshed since they were culled against it.
+    /// The tiles are kept aside for [`World::restore_chunk`].
+    pub fn remove_chunk(&mut self, key: (i32, i32, i32)) {
+        if !self.chunks.contains_key(&key) {
+            return;
+        }
+
+        self.park_chunk(key);
+        self.mark_dirty_with_neighbours(key);
+        self.dirty.remove(&key);
+    }
+
+    ///
+    /// Loads a chunk again with the tiles and buildings it had when it was removed, or empty if it never was loaded.
+    /// Returns whether DFHack already sent blocks of the chunk, it only sends their changes from then on.
+    pub fn restore_chunk(&mut self, key: (i32, i32, i32)) -> bool {
+        let restored = match self.removed.remove(&key) {
+            Some(removed) => {
+                self.chunks.insert(key, removed.chunk);
+                for building in removed.buildings {
+                    self.changed_buildings.insert(building.index);
+                    self.buildings.insert(building.index, building);
+                }
+                true
+            },
+            None => false,
+        };
+        self.chunk_mut(key);
+        restored
+    }
+
+    ///
+    /// Whether DFHack's map hashes have to be reset before the chunk at `key` is requested,
+    /// because its parked tiles were dropped and DFHack would only send the blocks that changed since.
+    /// The caller is expected to reset them, so every parked chunk is dropped as well, DFHack sends all of them again.
+    pub fn needs_hash_reset(&mut self, key: (i32, i32, i32)) -> bool {
+        if !self.evicted.contains(&key) {
+            return false;
+        }
+        self.evicted.clear();
+        self.removed.clear();
+        true
+    }
+
+    fn park_chunk(&mut self, key: (i32, i32, i32)) {
+        let Some(mut chunk) = self.chunks.remove(&key) else {
+            return;
+        };
+        //The entity and meshes are despawned with the chunk, they are created again when it is restored
+        chunk.meshes = None;
+
+        let indices = self.buildings
+            .iter()
+            .filter(|(_, building)| building.chunk_key() == key)
+            .map(|(index, _)| *index)
+            .collect::<Vec<_>>();
+        let mut buildings = Vec::new();
+        for index in indices {
+            buildings.extend(self.buildings.remove(&index));
+            self.changed_buildings.insert(index);
+        }
+
+        self.parked += 1;
+        self.removed.insert(key, RemovedChunk { chunk, buildings, parked: self.parked });
+
+        if self.removed.len() > MAX_REMOVED_CHUNKS {
+            let oldest = self.removed
+                .iter()
+                .min_by_key(|(_, removed)| removed.parked)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.removed.remove(&oldest);
+                self.evicted.insert(oldest);
+            }
         }
     }
 
+    pub fn get_chunk(&self, key: (i32, i32, i32)) -> Option<&Chunk> {
+        self.chunks.get(&key).map(|x| x.as_ref())
+    }
+
+    ///
+    /// Looks up a tile relative to the chunk at `key`, coordinates outside 0..16 reach into the neighbouring chunks.
+    /// Returns None if that chunk isn't loaded.
+    pub fn tile_at(&self, key: (i32, i32, i32), x: i32, y: i32, z: i32) -> Option<&Tile>{
+        let key = (key.0 + x.div_euclid(16), key.1 + y.div_euclid(16), key.2 + z.div_euclid(16));
+        self.get_chunk(key).map(|chunk| chunk.tile_ref(x.rem_euclid(16), y.rem_euclid(16), z.rem_euclid(16)))
+    }
+
     pub fn chunk_mut(&mut self, key: (i32, i32, i32)) -> &mut Chunk {
         match self.chunks.entry(key) {
             Entry::Vacant(entry) => entry.insert(Box::new(Chunk::new())),
@@ -41,6 +317,8 @@ impl World {
 pub struct Chunk {
     tiles: [Tile; 4096],
     pub id: Entity,
+    /// One mesh per [`MeshLayer`], created once the chunk has been loaded.
+    pub meshes: Option<[Handle<Mesh>; LAYER_COUNT]>,
 }
 
 impl Chunk {
@@ -48,6 +326,7 @@ impl Chunk {
         Self {
             tiles: [Tile::default(); 4096],
             id: Entity::from_raw(0),
+            meshes: None,
         }
     }
 
@@ -59,53 +338,12 @@ impl Chunk {
         &self.tiles[(x + y * 16 + z * 256) as usize]
     }
 
-    pub fn get_mask(&self, x: i32, y: i32, z: i32, registry: &MaterialRegistry) -> u8{
-        let up = self.is_solid(x, y+1, z, Direction::Up, registry);
-        let down = self.is_solid(x, y-1, z, Direction::Down, registry);
-        let left = self.is_solid(x+1, y, z, Direction::Left, registry);
-        let right = self.is_solid(x-1, y, z, Direction::Right, registry);
-        let forward = self.is_solid(x, y, z+1, Direction::Forward, registry);
-        let backward = self.is_solid(x, y, z-1, Direction::Backwards, registry);
-
-        ((up as u8)      << 5) |
-        ((down as u8)    << 4) |
-        ((left as u8)    << 3) |
-        ((right as u8)   << 2) |
-        ((forward as u8) << 1) |
-        ((backward as u8))
-    }
-
-    pub fn is_solid(&self, x: i32, y: i32, z: i32, direction: Direction, registry: &MaterialRegistry) -> bool{
+    pub fn is_solid(&self, x: i32, y: i32, z: i32, direction: Direction, registry: &MaterialRegistry, models: &ModelRegistry) -> bool{
         if x < 0 || x >= 16 || y < 0 || y >= 16 || z < 0 || z >= 16{
             false
         }
         else{
-            let tile = self.tile_ref(x, y, z);
-            let type_ = registry.get_tiletype(tile);
-            tile.hidden || match type_.shape {
-                TiletypeShape::Sapling |
-                TiletypeShape::Shrub |
-                TiletypeShape::EndlessPit |
-                TiletypeShape::NoShape |
-                TiletypeShape::Empty => false,
-                
-                TiletypeShape::Boulder |
-                TiletypeShape::Pebbles |
-                TiletypeShape::Floor => direction == Direction::Up,
-                TiletypeShape::Fortification |
-                TiletypeShape::StairUp |
-                TiletypeShape::StairDown |
-                TiletypeShape::StairUpdown |
-                TiletypeShape::Ramp |
-                TiletypeShape::RampTop |
-                TiletypeShape::BrookBed |
-                TiletypeShape::BrookTop |
-                TiletypeShape::TreeShape |
-                TiletypeShape::Branch |
-                TiletypeShape::TrunkBranch |
-                TiletypeShape::Twig |
-                TiletypeShape::Wall => true,
-            }
+            registry.is_solid(self.tile_ref(x, y, z), direction, models)
         }
     }
 }
@@ -133,6 +371,8 @@ impl From<MatPair> for Matpair{
 pub struct MaterialDef{
     pub id: Option<Identifier>,
     pub mat_pair: Matpair,
+    /// DF state colour, used by models with `Tint(true)`.
+    pub color: Color,
 }
 
 #[derive(Debug)]
@@ -164,10 +404,12 @@ impl From<Tiletype> for FixedTiletype{
     }
 }
 
-#[derive(Resource)]
+///
+/// Cloning is cheap, the definitions are shared with the meshing tasks.
+#[derive(Resource, Clone)]
 pub struct MaterialRegistry{
-    matdefs: BTreeMap<Matpair, MaterialDef>,
-    tiletypes: Vec<FixedTiletype>
+    matdefs: Arc<BTreeMap<Matpair, MaterialDef>>,
+    tiletypes: Arc<Vec<FixedTiletype>>
 }
 
 impl MaterialRegistry{
@@ -175,33 +417,280 @@ impl MaterialRegistry{
     pub fn get_tiletype(&self, tile: &Tile) -> &FixedTiletype{
         &self.tiletypes[tile.tile_id as usize]
     }
+
+    pub fn get_color(&self, mat_pair: &Matpair) -> Color{
+        self.matdefs.get(mat_pair).map_or(Color::WHITE, |x| x.color)
+    }
+
+    fn get_model<'a>(&self, tile: &Tile, models: &'a ModelRegistry) -> Option<&'a ModelEntry>{
+        let id = self.matdefs.get(&tile.base_mat)?.id.as_ref()?;
+        models.get_model_and_cache(id, self.get_tiletype(tile).shape)
+    }
+
+    ///
+    /// Whether two tiles are filled with the same transparent model, like two panes of one glass wall.
+    /// The faces between them are culled as if they were solid, so the wall isn't drawn as a stack of boxes.
+    pub fn shares_transparent_model(&self, tile: &Tile, neighbour: &Tile, models: &ModelRegistry) -> bool{
+        match (self.get_model(tile, models), self.get_model(neighbour, models)) {
+            (Some(a), Some(b)) => a.0.transparent && std::ptr::eq(a, b),
+            _ => false,
+        }
+    }
+
+    ///
+    /// Whether `tile` hides the face of its neighbour that looks at it from `direction`.
+    /// The `visibility` of the tile's model wins, otherwise tiles with a transparent model, like glass or ice walls, don't hide anything.
+    pub fn is_solid(&self, tile: &Tile, direction: Direction, models: &ModelRegistry) -> bool{
+        if tile.hidden{
+            return true;
+        }
+
+        let type_ = self.get_tiletype(tile);
+        if let Some(model) = self.get_model(tile, models){
+            //The neighbour looks at the side of this tile facing back towards it
+            if let Some(solid) = model.0.visibility.get(&direction.opposite()){
+                return *solid;
+            }
+            if model.0.transparent{
+                return false;
+            }
+        }
+
+        match type_.shape {
+            TiletypeShape::Sapling |
+            TiletypeShape::Shrub |
+            TiletypeShape::EndlessPit |
+            TiletypeShape::NoShape |
+            TiletypeShape::Empty => false,
+            
+            TiletypeShape::Boulder |
+            TiletypeShape::Pebbles |
+            TiletypeShape::Floor => direction == Direction::Up,
+            TiletypeShape::Fortification |
+            TiletypeShape::StairUp |
+            TiletypeShape::StairDown |
+            TiletypeShape::StairUpdown |
+            TiletypeShape::Ramp |
+            TiletypeShape::RampTop |
+            TiletypeShape::BrookBed |
+            TiletypeShape::BrookTop |
+            TiletypeShape::TreeShape |
+            TiletypeShape::Branch |
+            TiletypeShape::TrunkBranch |
+            TiletypeShape::Twig |
+            TiletypeShape::Wall => true,
+        }
+    }
 }
 
 impl FromWorld for MaterialRegistry{
     fn from_world(world: &mut bevy::prelude::World) -> Self {
-        let mut client = world.get_resource_mut::<FortressResource>().unwrap();
-        let matdefs: BTreeMap<Matpair, MaterialDef> = client.0.get_material_list().material_list.into_iter().map(
+        let mut client = world.resource::<FortressResource>().lock();
+        let matdefs: BTreeMap<Matpair, MaterialDef> = client.get_material_list().material_list.into_iter().map(
             |x|{
                 let id = x.id.map(
                     |y|
                     Identifier::from(String::from_utf8(y).unwrap())
                 );
                 let mp = x.mat_pair.into();
+                let color = x.state_color.map_or(Color::WHITE, |c| Color::rgb_u8(c.red as u8, c.green as u8, c.blue as u8));
                 (
                     mp,
                     MaterialDef{
                         id,
-                        mat_pair: mp
+                        mat_pair: mp,
+                        color,
                     }
                 )
             }
         ).collect();
 
-        let tiletypes = client.0.get_tile_type_list().tiletype_list.into_iter().map(|x| x.into()).collect();
+        let tiletypes: Vec<FixedTiletype> = client.get_tile_type_list().tiletype_list.into_iter().map(|x| x.into()).collect();
         
         Self {
-            matdefs,
-            tiletypes
+            matdefs: Arc::new(matdefs),
+            tiletypes: Arc::new(tiletypes)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use df_rust::clients::remote_fortress_reader::remote_fortress_reader::{BuildingInstance, MapBlock, MatPair};
+
+    use super::{BlockContents, Matpair, World, MAX_REMOVED_CHUNKS};
+
+    fn building(index: i32, x: i32, y: i32, z: i32) -> BuildingInstance {
+        BuildingInstance {
+            index,
+            pos_x_min: Some(x),
+            pos_y_min: Some(y),
+            pos_z_min: Some(z),
+            pos_x_max: Some(x),
+            pos_y_max: Some(y),
+            pos_z_max: Some(z),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_building_list_removes_the_last_building(){
+        //ARRANGE
+        let mut world = World::new();
+        world.chunk_mut((1, 0, 2));
+        world.apply_block(&MapBlock {
+            map_x: 16,
+            map_y: 32,
+            map_z: 3,
+            tiles: vec![1; 256],
+            buildings: vec![building(7, 18, 33, 3)],
+            ..Default::default()
+        }, BlockContents::Full);
+        world.take_changed_buildings();
+
+        //ACT
+        world.apply_block(&MapBlock {
+            map_x: 16,
+            map_y: 32,
+            map_z: 3,
+            ..Default::default()
+        }, BlockContents::Changes);
+
+        //ASSERT
+        assert!(world.get_building(7).is_none());
+        assert_eq!(world.take_changed_buildings(), vec![7]);
+    }
+
+    #[test]
+    fn tile_changes_keep_the_buildings(){
+        //ARRANGE
+        let mut world = World::new();
+        world.chunk_mut((1, 0, 2));
+        world.apply_block(&MapBlock {
+            map_x: 16,
+            map_y: 32,
+            map_z: 3,
+            buildings: vec![building(7, 18, 33, 3)],
+            ..Default::default()
+        }, BlockContents::Full);
+        world.take_changed_buildings();
+
+        //ACT
+        world.apply_block(&MapBlock {
+            map_x: 16,
+            map_y: 32,
+            map_z: 3,
+            tiles: vec![2; 256],
+            ..Default::default()
+        }, BlockContents::Changes);
+
+        //ASSERT
+        assert!(world.get_building(7).is_some());
+        assert!(world.take_changed_buildings().is_empty());
+    }
+
+    #[test]
+    fn unloaded_chunk_is_restored_with_its_tiles_and_buildings(){
+        //ARRANGE
+        let mut world = World::new();
+        world.chunk_mut((1, 0, 2));
+        world.apply_block(&MapBlock {
+            map_x: 16,
+            map_y: 32,
+            map_z: 3,
+            tiles: vec![5; 256],
+            buildings: vec![building(7, 18, 33, 3)],
+            ..Default::default()
+        }, BlockContents::Full);
+
+        //ACT
+        world.remove_chunk((1, 0, 2));
+        let unloaded = world.get_chunk((1, 0, 2)).is_none() && world.get_building(7).is_none();
+        let restored = world.restore_chunk((1, 0, 2));
+
+        //ASSERT
+        assert!(unloaded);
+        assert!(restored);
+        assert_eq!(world.chunk((1, 0, 2)).tile_ref(0, 3, 0).tile_id, 5);
+        assert!(world.get_building(7).is_some());
+        assert!(!world.needs_hash_reset((1, 0, 2)));
+    }
+
+    #[test]
+    fn chunks_unloaded_longest_ago_are_dropped(){
+        //ARRANGE
+        let mut world = World::new();
+        for x in 0..=MAX_REMOVED_CHUNKS as i32 {
+            world.chunk_mut((x, 0, 0));
+        }
+
+        //ACT
+        for x in 0..=MAX_REMOVED_CHUNKS as i32 {
+            world.remove_chunk((x, 0, 0));
+        }
+        world.apply_block(&MapBlock {
+            tiles: vec![5; 256],
+            ..Default::default()
+        }, BlockContents::Changes);
+
+        //ASSERT
+        assert!(!world.restore_chunk((0, 0, 0)));
+        assert_eq!(world.chunk((0, 0, 0)).tile_ref(0, 0, 0).tile_id, 0);
+        assert!(world.needs_hash_reset((0, 0, 0)));
+        assert!(!world.restore_chunk((1, 0, 0)));
+    }
+
+    #[test]
+    fn tiles_left_out_of_a_block_are_kept(){
+        //ARRANGE
+        let mut world = World::new();
+        world.chunk_mut((1, 0, 2));
+        world.apply_block(&MapBlock {
+            map_x: 16,
+            map_y: 32,
+            map_z: 3,
+            tiles: vec![5; 256],
+            hidden: vec![true; 256],
+            ..Default::default()
+        }, BlockContents::Full);
+
+        //ACT
+        world.apply_block(&MapBlock {
+            map_x: 16,
+            map_y: 32,
+            map_z: 3,
+            materials: vec![MatPair { mat_type: 0, mat_index: 4 }; 256],
+            ..Default::default()
+        }, BlockContents::Changes);
+
+        //ASSERT
+        let tile = world.chunk((1, 0, 2)).tile_ref(15, 3, 15);
+        assert_eq!(tile.tile_id, 5);
+        assert!(tile.hidden);
+        assert_eq!(tile.mat_pair, Matpair { type_: 0, index: 4 });
+    }
+
+    #[test]
+    fn blocks_of_unloaded_chunks_are_kept_until_they_are_restored(){
+        //ARRANGE
+        let mut world = World::new();
+
+        //ACT
+        world.apply_block(&MapBlock {
+            map_x: 16,
+            map_y: 32,
+            map_z: 3,
+            tiles: vec![5; 256],
+            buildings: vec![building(7, 18, 33, 3)],
+            ..Default::default()
+        }, BlockContents::Full);
+        let loaded = world.get_chunk((1, 0, 2)).is_some();
+        let restored = world.restore_chunk((1, 0, 2));
+
+        //ASSERT
+        assert!(!loaded);
+        assert!(restored);
+        assert_eq!(world.chunk((1, 0, 2)).tile_ref(0, 3, 0).tile_id, 5);
+        assert!(world.get_building(7).is_some());
+    }
+}
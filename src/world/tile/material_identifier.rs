@@ -91,6 +91,10 @@ impl Identifier{
         (self.0).1 == 0
     }
 
+    pub fn elements(&self) -> &[MaterialIdentifierElement]{
+        &(self.0).0[0..(self.0).1 as usize]
+    }
+
     pub fn last(&self) -> Option<&str>{
         if (self.0).1 == 0{
             None
@@ -41,4 +41,27 @@ pub struct Tile {
     pub mat_pair: Matpair,
     pub base_mat: Matpair,
     pub hidden: bool,
+    /// Liquid depths from 0 to 7, as in the `water` and `magma` fields of a map block.
+    pub water: u8,
+    pub magma: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Liquid {
+    Water,
+    Magma,
+}
+
+impl Tile {
+    ///
+    /// The liquid filling this tile and its depth, from 1 to 7.
+    pub fn liquid(&self) -> Option<(Liquid, u8)> {
+        if self.magma > 0 {
+            Some((Liquid::Magma, self.magma.min(7)))
+        } else if self.water > 0 {
+            Some((Liquid::Water, self.water.min(7)))
+        } else {
+            None
+        }
+    }
 }
@@ -0,0 +1,62 @@
+use bevy::prelude::{FromWorld, IVec3, Resource, Vec3};
+use df_rust::clients::remote_fortress_reader::remote_fortress_reader::UnitDefinition;
+
+use crate::fortress::FortressResource;
+
+use super::tile::material_identifier::Identifier;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub id: i32,
+    /// DF tile coordinates.
+    pub position: IVec3,
+    pub race: i32,
+}
+
+impl Unit {
+    ///
+    /// Dead and departed units are still listed by DFHack, they are either flagged invalid or moved off the map.
+    pub fn from_definition(x: &UnitDefinition) -> Option<Self> {
+        if !x.is_valid() || x.pos_x() < 0 || x.pos_y() < 0 || x.pos_z() < 0 {
+            return None;
+        }
+
+        Some(Self {
+            id: x.id,
+            position: IVec3::new(x.pos_x(), x.pos_y(), x.pos_z()),
+            race: x.race.as_ref().map_or(-1, |r| r.mat_type),
+        })
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        //Flipping coordinates
+        Vec3::new(self.position.x as f32, self.position.z as f32, self.position.y as f32)
+    }
+}
+
+///
+/// Creature ids by race index, DWARF, CAT, GOBLIN...
+#[derive(Resource)]
+pub struct CreatureRegistry {
+    races: Vec<Option<Identifier>>,
+}
+
+impl CreatureRegistry {
+    pub fn get_identifier(&self, race: i32) -> Option<&Identifier> {
+        usize::try_from(race).ok()
+            .and_then(|x| self.races.get(x))
+            .and_then(|x| x.as_ref())
+    }
+}
+
+impl FromWorld for CreatureRegistry {
+    fn from_world(world: &mut bevy::prelude::World) -> Self {
+        let mut client = world.resource::<FortressResource>().lock();
+        //Raws are sent in race order
+        let races = client.get_creature_raws().creature_raws.into_iter().map(
+            |x| x.creature_id.map(|y| Identifier::from(String::from_utf8_lossy(&y).into_owned()))
+        ).collect();
+
+        Self { races }
+    }
+}